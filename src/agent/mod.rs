@@ -1,20 +1,122 @@
+use crate::config::configured_agent_command;
 use crate::prompt::PromptManager;
 use crate::worktree::WorktreeInfo;
 use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::Path;
 use std::process::{Command, Stdio};
 
+/// How a system prompt is combined with the agent's built-in one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptMode {
+    /// Replace the agent's default system prompt entirely
+    #[default]
+    Replace,
+    /// Keep the default system prompt and add ours after it
+    Append,
+}
+
+/// A named prompt from the prompt library and how to apply it.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemPrompt<'a> {
+    pub name: &'a str,
+    pub mode: PromptMode,
+}
+
+/// Optional features of an agent CLI that maokai can drive.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct AgentCapabilities {
+    /// A prompt from the library can be passed to it, in whatever way the agent reads one
+    pub supports_system_prompt: bool,
+    /// The model can be chosen with a forwarded agent argument (e.g. `--model`)
+    pub supports_model: bool,
+    /// maokai's `--mcp-config` file can be handed to it
+    pub supports_mcp: bool,
+}
+
 pub trait Agent {
     fn name(&self) -> &str;
     fn command(&self) -> &str;
+    /// Human-readable name used in messages, e.g. "Claude".
+    fn label(&self) -> &str;
+    fn capabilities(&self) -> AgentCapabilities;
+
+    /// Build the process that runs the agent in the worktree, without starting it.
+    fn build_command(
+        &self,
+        worktree_info: &WorktreeInfo,
+        system_prompt: Option<SystemPrompt<'_>>,
+        mcp_config: Option<&Path>,
+        agent_env: &BTreeMap<String, String>,
+        agent_args: &[String],
+    ) -> Result<Command>;
+
+    /// Run the agent in the foreground and wait for it to exit.
     fn start(
         &self,
         worktree_info: &WorktreeInfo,
-        system_prompt: Option<&str>,
+        system_prompt: Option<SystemPrompt<'_>>,
+        mcp_config: Option<&Path>,
+        agent_env: &BTreeMap<String, String>,
         agent_args: &[String],
-    ) -> Result<()>;
+    ) -> Result<()> {
+        println!(
+            "Starting {} agent for branch: {}",
+            self.label(),
+            worktree_info.branch
+        );
+        println!("Worktree path: {}", worktree_info.path.display());
+        let tty_note = check_tty();
+
+        let mut cmd = self.build_command(
+            worktree_info,
+            system_prompt,
+            mcp_config,
+            agent_env,
+            agent_args,
+        )?;
+        cmd.stdin(Stdio::inherit());
+        cmd.stdout(Stdio::inherit());
+        cmd.stderr(Stdio::inherit());
+
+        let status = cmd
+            .status()
+            .with_context(|| format!("Failed to start {} agent{}", self.label(), tty_note))?;
+
+        if !status.success() {
+            anyhow::bail!("{} agent exited with error{}", self.label(), tty_note);
+        }
+
+        Ok(())
+    }
 }
 
-pub struct ClaudeAgent;
+/// Agents are interactive programs; launched from cron or CI they tend to hang or fail
+/// obscurely. Warn up front and return a hint to append to launch errors.
+fn check_tty() -> &'static str {
+    if std::io::stdin().is_terminal() {
+        return "";
+    }
+    eprintln!(
+        "Warning: agent launched without a TTY on stdin; it may not be interactive. Use --no-agent in scripts."
+    );
+    " (stdin is not a terminal)"
+}
+
+pub struct ClaudeAgent {
+    command: String,
+}
+
+impl Default for ClaudeAgent {
+    fn default() -> Self {
+        Self {
+            command: "claude".to_string(),
+        }
+    }
+}
 
 impl Agent for ClaudeAgent {
     fn name(&self) -> &str {
@@ -22,70 +124,179 @@ impl Agent for ClaudeAgent {
     }
 
     fn command(&self) -> &str {
-        "claude"
+        &self.command
     }
 
-    fn start(
+    fn label(&self) -> &str {
+        "Claude"
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            supports_system_prompt: true,
+            supports_model: true,
+            supports_mcp: true,
+        }
+    }
+
+    fn build_command(
         &self,
         worktree_info: &WorktreeInfo,
-        system_prompt: Option<&str>,
+        system_prompt: Option<SystemPrompt<'_>>,
+        mcp_config: Option<&Path>,
+        agent_env: &BTreeMap<String, String>,
         agent_args: &[String],
-    ) -> Result<()> {
-        println!("Starting Claude agent for branch: {}", worktree_info.branch);
-        println!("Worktree path: {}", worktree_info.path.display());
-
+    ) -> Result<Command> {
         let mut cmd = Command::new(self.command());
 
         // Add forwarded agent arguments
         cmd.args(agent_args);
 
-        if let Some(prompt_name) = system_prompt {
+        if let Some(prompt) = system_prompt {
             let prompt_manager = PromptManager::new()?;
             let prompt_content = prompt_manager
-                .load_prompt(prompt_name)
-                .with_context(|| format!("Failed to load system prompt: {}", prompt_name))?;
+                .load_prompt(prompt.name)
+                .with_context(|| format!("Failed to load system prompt: {}", prompt.name))?;
 
-            println!("Using system prompt: {}", prompt_name);
-            cmd.arg("--system-prompt").arg(prompt_content);
+            let flag = match prompt.mode {
+                PromptMode::Replace => "--system-prompt",
+                PromptMode::Append => "--append-system-prompt",
+            };
+            println!("Using system prompt: {} ({})", prompt.name, flag);
+            cmd.arg(flag).arg(prompt_content);
         }
 
+        if let Some(mcp_config) = mcp_config {
+            println!("Using MCP config: {}", mcp_config.display());
+            cmd.arg("--mcp-config").arg(mcp_config);
+        }
+
+        cmd.envs(agent_env);
         cmd.current_dir(&worktree_info.path);
-        cmd.stdin(Stdio::inherit());
-        cmd.stdout(Stdio::inherit());
-        cmd.stderr(Stdio::inherit());
+        Ok(cmd)
+    }
+}
 
-        let status = cmd.status().context("Failed to start Claude agent")?;
+pub struct GeminiAgent {
+    command: String,
+}
 
-        if !status.success() {
-            anyhow::bail!("Claude agent exited with error");
+impl Default for GeminiAgent {
+    fn default() -> Self {
+        Self {
+            command: "gemini".to_string(),
         }
-
-        Ok(())
     }
 }
 
-pub struct GeminiAgent;
-
 impl Agent for GeminiAgent {
     fn name(&self) -> &str {
         "gemini"
     }
 
     fn command(&self) -> &str {
-        "gemini"
+        &self.command
     }
 
-    fn start(
+    fn label(&self) -> &str {
+        "Gemini"
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            supports_system_prompt: true,
+            supports_model: true,
+            ..Default::default()
+        }
+    }
+
+    fn build_command(
         &self,
         worktree_info: &WorktreeInfo,
-        system_prompt: Option<&str>,
+        system_prompt: Option<SystemPrompt<'_>>,
+        mcp_config: Option<&Path>,
+        agent_env: &BTreeMap<String, String>,
         agent_args: &[String],
-    ) -> Result<()> {
-        println!("Starting Gemini agent for branch: {}", worktree_info.branch);
-        println!("Worktree path: {}", worktree_info.path.display());
+    ) -> Result<Command> {
+        if mcp_config.is_some() {
+            anyhow::bail!(
+                "Gemini agent does not support --mcp-config (configure MCP servers in its settings.json)"
+            );
+        }
+
+        let mut cmd = Command::new(self.command());
 
-        if system_prompt.is_some() {
-            anyhow::bail!("Gemini agent does not support system prompts");
+        // Add forwarded agent arguments
+        cmd.args(agent_args);
+
+        // Gemini reads a replacement system prompt from the file named by GEMINI_SYSTEM_MD
+        if let Some(prompt) = system_prompt {
+            if prompt.mode == PromptMode::Append {
+                anyhow::bail!(
+                    "Gemini agent can only replace its system prompt; drop --append-prompt"
+                );
+            }
+            let prompt_manager = PromptManager::new()?;
+            prompt_manager
+                .load_prompt(prompt.name)
+                .with_context(|| format!("Failed to load system prompt: {}", prompt.name))?;
+
+            println!("Using system prompt: {} (GEMINI_SYSTEM_MD)", prompt.name);
+            cmd.env(
+                "GEMINI_SYSTEM_MD",
+                prompt_manager.get_prompt_path(prompt.name),
+            );
+        }
+
+        cmd.envs(agent_env);
+        cmd.current_dir(&worktree_info.path);
+        Ok(cmd)
+    }
+}
+
+pub struct AiderAgent {
+    command: String,
+}
+
+impl Default for AiderAgent {
+    fn default() -> Self {
+        Self {
+            command: "aider".to_string(),
+        }
+    }
+}
+
+impl Agent for AiderAgent {
+    fn name(&self) -> &str {
+        "aider"
+    }
+
+    fn command(&self) -> &str {
+        &self.command
+    }
+
+    fn label(&self) -> &str {
+        "Aider"
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            supports_system_prompt: true,
+            supports_model: true,
+            ..Default::default()
+        }
+    }
+
+    fn build_command(
+        &self,
+        worktree_info: &WorktreeInfo,
+        system_prompt: Option<SystemPrompt<'_>>,
+        mcp_config: Option<&Path>,
+        agent_env: &BTreeMap<String, String>,
+        agent_args: &[String],
+    ) -> Result<Command> {
+        if mcp_config.is_some() {
+            anyhow::bail!("Aider agent does not support --mcp-config");
         }
 
         let mut cmd = Command::new(self.command());
@@ -93,26 +304,440 @@ impl Agent for GeminiAgent {
         // Add forwarded agent arguments
         cmd.args(agent_args);
 
+        // Aider keeps its own system prompt; ours is added as read-only context
+        if let Some(prompt) = system_prompt {
+            let prompt_manager = PromptManager::new()?;
+            prompt_manager
+                .load_prompt(prompt.name)
+                .with_context(|| format!("Failed to load system prompt: {}", prompt.name))?;
+
+            println!("Using system prompt: {} (--read)", prompt.name);
+            cmd.arg("--read")
+                .arg(prompt_manager.get_prompt_path(prompt.name));
+        }
+
+        cmd.envs(agent_env);
         cmd.current_dir(&worktree_info.path);
-        cmd.stdin(Stdio::inherit());
-        cmd.stdout(Stdio::inherit());
-        cmd.stderr(Stdio::inherit());
+        Ok(cmd)
+    }
+}
+
+/// Runs another agent inside a Docker or Podman container. The worktree is mounted at
+/// `/work`, and the main repository's `.git` at its host path so git keeps working.
+/// The MCP config and system prompt file are mounted read-only at their host paths.
+pub struct ContainerAgent {
+    inner: Box<dyn Agent>,
+    image: String,
+    runtime: String,
+}
 
-        let status = cmd.status().context("Failed to start Gemini agent")?;
+/// Mount point of the worktree inside the container.
+const CONTAINER_WORKDIR: &str = "/work";
 
-        if !status.success() {
-            anyhow::bail!("Gemini agent exited with error");
+impl ContainerAgent {
+    /// Wrap `inner`, using `docker` if installed and `podman` otherwise.
+    pub fn new(inner: Box<dyn Agent>, image: &str) -> Result<Self> {
+        let runtime = ["docker", "podman"]
+            .into_iter()
+            .find(|runtime| is_installed(runtime))
+            .ok_or_else(|| anyhow::anyhow!("--container requires docker or podman on PATH"))?;
+
+        Ok(Self {
+            inner,
+            image: image.to_string(),
+            runtime: runtime.to_string(),
+        })
+    }
+}
+
+impl Agent for ContainerAgent {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn command(&self) -> &str {
+        self.inner.command()
+    }
+
+    fn label(&self) -> &str {
+        self.inner.label()
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn build_command(
+        &self,
+        worktree_info: &WorktreeInfo,
+        system_prompt: Option<SystemPrompt<'_>>,
+        mcp_config: Option<&Path>,
+        agent_env: &BTreeMap<String, String>,
+        agent_args: &[String],
+    ) -> Result<Command> {
+        let inner = self.inner.build_command(
+            worktree_info,
+            system_prompt,
+            mcp_config,
+            agent_env,
+            agent_args,
+        )?;
+
+        let mut cmd = Command::new(&self.runtime);
+        cmd.args(["run", "--rm", "-i"]);
+        if std::io::stdin().is_terminal() {
+            cmd.arg("-t");
         }
 
-        Ok(())
+        let mount = |cmd: &mut Command, host: &Path, target: &Path, read_only: bool| {
+            let mut spec = format!("{}:{}", host.display(), target.display());
+            if read_only {
+                spec.push_str(":ro");
+            }
+            cmd.arg("-v").arg(spec);
+        };
+        mount(
+            &mut cmd,
+            &worktree_info.path,
+            Path::new(CONTAINER_WORKDIR),
+            false,
+        );
+        let git_dir = worktree_info.project_root.join(".git");
+        if git_dir.is_dir() {
+            mount(&mut cmd, &git_dir, &git_dir, false);
+        }
+        if let Some(mcp_config) = mcp_config {
+            mount(&mut cmd, mcp_config, mcp_config, true);
+        }
+        // Gemini and Aider are handed the prompt file's host path, so make it exist there
+        if let Some(prompt) = system_prompt {
+            let prompt_path = PromptManager::new()?.get_prompt_path(prompt.name);
+            mount(&mut cmd, &prompt_path, &prompt_path, true);
+        }
+        cmd.args(["-w", CONTAINER_WORKDIR]);
+
+        // Pass variables by name only and set their values on docker's own environment,
+        // so secrets never appear in the process list or shell history.
+        for (key, value) in worktree_info.env_vars() {
+            let value = match key {
+                "MAOKAI_WORKTREE_PATH" => CONTAINER_WORKDIR.to_string(),
+                _ => value,
+            };
+            cmd.arg("-e").arg(key).env(key, value);
+        }
+        for (key, value) in inner.get_envs() {
+            if let Some(value) = value {
+                cmd.arg("-e").arg(key).env(key, value);
+            }
+        }
+
+        cmd.arg(&self.image);
+        cmd.arg(inner.get_program());
+        cmd.args(inner.get_args());
+        Ok(cmd)
+    }
+}
+
+/// A `major.minor.patch` version as printed by an agent CLI's `--version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AgentVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl AgentVersion {
+    /// Parse the first version-looking word in `text`, e.g. `1.0.43 (Claude Code)` or
+    /// `v0.2`. Missing minor/patch components count as zero.
+    pub fn parse(text: &str) -> Option<Self> {
+        text.split(|c: char| c.is_whitespace() || c == '(' || c == ',')
+            .find_map(|word| {
+                let word = word.trim_start_matches('v');
+                let core = word.split(['-', '+']).next()?;
+                let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+                let major = parts.next()??;
+                let minor = parts.next().unwrap_or(Some(0))?;
+                let patch = parts.next().unwrap_or(Some(0))?;
+                Some(Self {
+                    major,
+                    minor,
+                    patch,
+                })
+            })
+    }
+}
+
+impl fmt::Display for AgentVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
     }
 }
 
+/// The version reported by `<agent> --version`, if it runs and prints one.
+pub fn installed_version(agent: &dyn Agent) -> Option<AgentVersion> {
+    Command::new(agent.command())
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| AgentVersion::parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Run `<agent> --version` and bail if it reports a version older than `min_version`.
+/// If the version cannot be determined, warn and carry on rather than block the user.
+pub fn check_min_version(agent: &dyn Agent, min_version: &str) -> Result<()> {
+    let required = AgentVersion::parse(min_version).with_context(|| {
+        format!(
+            "Invalid minimum version '{}' for agent {}",
+            min_version,
+            agent.name()
+        )
+    })?;
+
+    match installed_version(agent) {
+        Some(installed) if installed < required => anyhow::bail!(
+            "{} {} is installed, but this project requires at least {} (agent_min_version)",
+            agent.command(),
+            installed,
+            required
+        ),
+        Some(_) => {}
+        None => eprintln!(
+            "Warning: could not determine the {} version; skipping the minimum version check ({})",
+            agent.command(),
+            required
+        ),
+    }
+
+    Ok(())
+}
+
+/// Agents in the order `auto` tries them.
+pub const AGENT_NAMES: &[&str] = &["claude", "gemini", "aider"];
+
+/// Whether `command` resolves to an executable file: directly if it is a path,
+/// otherwise on `PATH`.
+pub fn is_installed(command: &str) -> bool {
+    if command.contains(std::path::MAIN_SEPARATOR) {
+        return is_executable(Path::new(command));
+    }
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| is_executable(&dir.join(command)))
+}
+
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|meta| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            meta.is_file() && meta.permissions().mode() & 0o111 != 0
+        }
+        #[cfg(not(unix))]
+        {
+            meta.is_file()
+        }
+    })
+}
+
+/// An agent's identity, availability and capabilities, as listed by `maokai agents`.
+#[derive(Debug, Serialize)]
+pub struct AgentDescriptor {
+    pub name: String,
+    pub command: String,
+    pub label: String,
+    /// Whether the command is on `PATH`
+    pub available: bool,
+    /// From `--version`; None if unavailable or unparseable
+    pub version: Option<String>,
+    #[serde(flatten)]
+    pub capabilities: AgentCapabilities,
+}
+
+impl AgentDescriptor {
+    /// Describe `agent`, running its `--version` if it is installed.
+    pub fn describe(agent: &dyn Agent) -> Self {
+        let available = is_installed(agent.command());
+        Self {
+            name: agent.name().to_string(),
+            command: agent.command().to_string(),
+            label: agent.label().to_string(),
+            available,
+            version: available
+                .then(|| installed_version(agent))
+                .flatten()
+                .map(|version| version.to_string()),
+            capabilities: agent.capabilities(),
+        }
+    }
+}
+
+/// Descriptors for every supported agent, in [`AGENT_NAMES`] order.
+pub fn describe_agents() -> Result<Vec<AgentDescriptor>> {
+    AGENT_NAMES
+        .iter()
+        .map(|name| Ok(AgentDescriptor::describe(get_agent(name)?.as_ref())))
+        .collect()
+}
+
+/// Resolve `auto` to the first installed agent; other names are returned unchanged.
+pub fn resolve_auto(agent_type: &str) -> Result<String> {
+    if agent_type != "auto" {
+        return Ok(agent_type.to_string());
+    }
+
+    for name in AGENT_NAMES {
+        let agent = get_agent(name)?;
+        if is_installed(agent.command()) {
+            return Ok(name.to_string());
+        }
+    }
+    anyhow::bail!(
+        "No supported agent found on PATH (tried {})",
+        AGENT_NAMES.join(", ")
+    )
+}
+
+/// Construct the agent named `agent_type`, running the executable configured in
+/// `[agent.<name>] command` if one is set.
 pub fn get_agent(agent_type: &str) -> Result<Box<dyn Agent>> {
+    if agent_type == "auto" {
+        return get_agent(&resolve_auto(agent_type)?);
+    }
+    let command =
+        configured_agent_command(agent_type)?.map(|command| command.to_string_lossy().into_owned());
     match agent_type {
-        "claude" => Ok(Box::new(ClaudeAgent)),
-        "gemini" => Ok(Box::new(GeminiAgent)),
+        "claude" => Ok(Box::new(match command {
+            Some(command) => ClaudeAgent { command },
+            None => ClaudeAgent::default(),
+        })),
+        "gemini" => Ok(Box::new(match command {
+            Some(command) => GeminiAgent { command },
+            None => GeminiAgent::default(),
+        })),
+        "aider" => Ok(Box::new(match command {
+            Some(command) => AiderAgent { command },
+            None => AiderAgent::default(),
+        })),
         _ => anyhow::bail!("Unknown agent type: {}", agent_type),
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worktree::WorktreeStatus;
+    use std::ffi::OsStr;
+    use std::path::PathBuf;
+
+    fn worktree_info() -> WorktreeInfo {
+        WorktreeInfo {
+            id: "test-id".to_string(),
+            branch: "feature".to_string(),
+            path: PathBuf::from("/nonexistent/maokai-test/wt"),
+            project_root: PathBuf::from("/nonexistent/maokai-test/project"),
+            project_name: "project".to_string(),
+            agent: "claude".to_string(),
+            created_at: chrono::Utc::now(),
+            status: WorktreeStatus::Active,
+            base_branch: None,
+            worktree_dir_name: "wt".to_string(),
+            pushed_to: None,
+            issue: None,
+        }
+    }
+
+    /// Write `name` into the test prompt library and return its path.
+    fn prompt_file(name: &str) -> PathBuf {
+        crate::config::test_home();
+        let path = PromptManager::new().unwrap().get_prompt_path(name);
+        std::fs::write(&path, "Be brief.").unwrap();
+        path
+    }
+
+    fn env_value<'a>(cmd: &'a Command, key: &str) -> Option<&'a OsStr> {
+        cmd.get_envs()
+            .find(|(k, _)| *k == key)
+            .and_then(|(_, value)| value)
+    }
+
+    #[test]
+    fn gemini_passes_system_prompt_via_env() {
+        let path = prompt_file("gemini-test");
+        let prompt = SystemPrompt {
+            name: "gemini-test",
+            mode: PromptMode::Replace,
+        };
+        let cmd = GeminiAgent::default()
+            .build_command(&worktree_info(), Some(prompt), None, &BTreeMap::new(), &[])
+            .unwrap();
+        assert_eq!(env_value(&cmd, "GEMINI_SYSTEM_MD"), Some(path.as_os_str()));
+
+        let cmd = GeminiAgent::default()
+            .build_command(&worktree_info(), None, None, &BTreeMap::new(), &[])
+            .unwrap();
+        assert_eq!(env_value(&cmd, "GEMINI_SYSTEM_MD"), None);
+    }
+
+    #[test]
+    fn aider_is_a_known_agent() {
+        crate::config::test_home();
+        let agent = get_agent("aider").unwrap();
+        assert_eq!(agent.name(), "aider");
+        assert_eq!(agent.command(), "aider");
+    }
+
+    #[test]
+    fn aider_reads_system_prompt_and_forwards_model() {
+        let path = prompt_file("aider-test");
+        let prompt = SystemPrompt {
+            name: "aider-test",
+            mode: PromptMode::Append,
+        };
+        let agent_args = ["--model".to_string(), "sonnet".to_string()];
+        let cmd = AiderAgent::default()
+            .build_command(
+                &worktree_info(),
+                Some(prompt),
+                None,
+                &BTreeMap::new(),
+                &agent_args,
+            )
+            .unwrap();
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                OsStr::new("--model"),
+                OsStr::new("sonnet"),
+                OsStr::new("--read"),
+                path.as_os_str()
+            ]
+        );
+    }
+
+    #[test]
+    fn container_mounts_the_prompt_file_at_its_host_path() {
+        let path = prompt_file("container-test");
+        let agent = ContainerAgent {
+            inner: Box::new(AiderAgent::default()),
+            image: "image".to_string(),
+            runtime: "docker".to_string(),
+        };
+        let prompt = SystemPrompt {
+            name: "container-test",
+            mode: PromptMode::Append,
+        };
+        let cmd = agent
+            .build_command(&worktree_info(), Some(prompt), None, &BTreeMap::new(), &[])
+            .unwrap();
+        let args: Vec<_> = cmd.get_args().collect();
+        let spec = format!("{}:{}:ro", path.display(), path.display());
+        assert!(args.windows(2).any(|pair| pair == ["-v", spec.as_str()]));
+        assert!(
+            args.windows(2)
+                .any(|pair| pair == [OsStr::new("--read"), path.as_os_str()])
+        );
+    }
+}
@@ -1,51 +1,530 @@
-use clap::{Parser, Subcommand, ValueEnum};
-use std::string::ToString;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::config::get_worktree_base_path;
+use crate::output::{RelativeTo, ShellFormat};
+use crate::worktree::{SortBy, SortOrder, WorktreeInfo, WorktreeManager};
 
 #[derive(Parser)]
 #[command(name = "maokai")]
 #[command(about = "Manage git worktrees with AI agents for parallel development")]
 pub struct Cli {
+    #[arg(long, global = true, help = "Disable colored output")]
+    pub no_color: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Scriptable mode: never prompt or pause, no color (also enabled by CI=true)"
+    )]
+    pub plain: bool,
+    #[arg(
+        long,
+        short = 'y',
+        global = true,
+        help = "Answer yes to every confirmation prompt"
+    )]
+    pub yes: bool,
+    #[arg(
+        long,
+        short = 'v',
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Log git commands, registry changes and file copies (-vv for more; RUST_LOG overrides)"
+    )]
+    pub verbose: u8,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "BASE",
+        num_args = 0..=1,
+        default_missing_value = "home",
+        require_equals = true,
+        help = "Print worktree paths relative to the home directory (~/...) or, with =cwd, the current directory"
+    )]
+    pub relative: Option<RelativeTo>,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+// Parsed once per run, so the size of `Create` doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
-    #[command(about = "Create a new worktree with optional custom command (use -- to separate)")]
+    #[command(
+        about = "Create a new worktree and launch an agent in it (agent arguments go after --)"
+    )]
     Create {
-        #[arg(help = "Branch name for the worktree")]
-        branch: String,
+        #[arg(
+            help = "Branch name for the worktree (default: a generated scratch/<timestamp> name)",
+            conflicts_with_all = ["stdin", "from_issue"]
+        )]
+        branch: Option<String>,
         #[arg(
             long,
-            help = "Agent to use (ignored if custom command provided)",
-            value_enum,
-            default_value_t = Agents::Claude
+            value_name = "NUMBER",
+            conflicts_with = "stdin",
+            help = "Name the branch after this GitHub issue (e.g. 123-fix-login-crash), fetched with gh"
+        )]
+        from_issue: Option<u64>,
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "move_changes",
+                "open_after",
+                "command",
+                "trailing_agent_args",
+                "complete_on_exit"
+            ],
+            help = "Read branch names from stdin, one per line, and create a worktree for each (implies --no-agent)"
+        )]
+        stdin: bool,
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "system_prompt",
+                "mcp_config",
+                "agent_args",
+                "trailing_agent_args",
+                "command",
+                "container",
+                "complete_on_exit"
+            ],
+            help = "Only create the worktree; do not launch an agent or custom command"
         )]
-        agent: Agents,
+        no_agent: bool,
+        #[arg(
+            long,
+            help = "Agent to use, or auto for the first one installed (defaults to repo/user config, then claude; ignored with --command)",
+            value_enum
+        )]
+        agent: Option<Agents>,
         #[arg(long, help = "Name of system prompt file in $HOME/maokai-prompts")]
         system_prompt: Option<String>,
+        #[arg(
+            long,
+            requires = "system_prompt",
+            help = "Append the system prompt to the agent's default one instead of replacing it"
+        )]
+        append_prompt: bool,
+        #[arg(
+            long,
+            help = "MCP server config file passed to the agent (Claude only)"
+        )]
+        mcp_config: Option<PathBuf>,
         #[arg(
             long,
             help = "Base branch to create the new branch from (defaults to current branch)"
         )]
         base_branch: Option<String>,
-        #[arg(last = true, help = "Custom command to run instead of agent (use -- to separate)")]
-        custom_command: Vec<String>,
+        #[arg(
+            long,
+            value_name = "NAME",
+            num_args = 0..=1,
+            default_missing_value = "origin",
+            require_equals = true,
+            help = "Base the branch on <remote>/<base-branch> and track it (default remote: origin)"
+        )]
+        remote: Option<String>,
+        #[arg(
+            long,
+            visible_alias = "force-fetch",
+            help = "Fetch the remote base branch before creating, so the worktree starts from its latest state"
+        )]
+        fetch: bool,
+        #[arg(
+            long,
+            alias = "from-stash",
+            help = "Move uncommitted changes from the current checkout into the new worktree"
+        )]
+        move_changes: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["move_changes", "stdin"],
+            help = "Copy uncommitted changes to tracked files into the new worktree, keeping them in the current checkout"
+        )]
+        snapshot_changes: bool,
+        #[arg(
+            long,
+            help = "If the worktree directory already exists, append a numeric suffix (-2, -3, ...) instead of failing"
+        )]
+        rename_on_conflict: bool,
+        #[arg(
+            long,
+            value_name = "DIR",
+            conflicts_with_all = ["stdin", "rename_on_conflict", "base_path_per_agent"],
+            help = "Create the worktree at this path instead of <base>/<project>-<branch>"
+        )]
+        path: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "move_changes",
+            help = "If a worktree for the branch already exists, reuse it instead of failing"
+        )]
+        attach_if_exists: bool,
+        #[arg(
+            long,
+            help = "Place the worktree under a directory named after the agent (<base>/<agent>/<name>)"
+        )]
+        base_path_per_agent: bool,
+        #[arg(
+            long,
+            help = "Copy git-ignored files (local settings, secrets) into the new worktree"
+        )]
+        copy_ignored: bool,
+        #[arg(
+            long,
+            value_name = "MESSAGE",
+            help = "Make an empty initial commit on the new branch (default message: \"Start <branch>\")"
+        )]
+        initial_commit: Option<Option<String>>,
+        #[arg(
+            long,
+            help = "Push the new branch with upstream tracking (to --remote, default origin); a failed push only warns"
+        )]
+        push: bool,
+        #[arg(long, help = "Like --push, but fail if the push fails")]
+        require_push: bool,
+        #[arg(
+            long,
+            help = "Before launching the agent, wait until the configured ready_check command succeeds"
+        )]
+        wait_ready: bool,
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 300,
+            requires = "wait_ready",
+            help = "How long --wait-ready waits before failing"
+        )]
+        timeout: u64,
+        #[arg(
+            long,
+            help = "Directory whose contents are copied into the new worktree"
+        )]
+        template: Option<PathBuf>,
+        #[arg(long, help = "Overwrite existing files when copying the template")]
+        force: bool,
+        #[arg(
+            long,
+            value_name = "IMAGE",
+            help = "Run the agent in a Docker/Podman container from IMAGE, with the worktree mounted at /work"
+        )]
+        container: Option<String>,
+        #[arg(
+            long,
+            value_enum,
+            help = "Open the worktree in the editor or a shell after the agent exits"
+        )]
+        open_after: Option<OpenAfter>,
+        #[arg(
+            long,
+            help = "Mark the worktree Completed when the agent exits successfully"
+        )]
+        complete_on_exit: bool,
+        #[arg(
+            long = "agent-arg",
+            value_name = "ARG",
+            allow_hyphen_values = true,
+            help = "Argument forwarded to the agent (repeatable, e.g. --agent-arg --model --agent-arg opus)"
+        )]
+        agent_args: Vec<String>,
+        #[arg(
+            long = "agent-env",
+            value_name = "KEY=VALUE",
+            help = "Environment variable set for the agent or custom command (repeatable)"
+        )]
+        agent_env: Vec<String>,
+        #[arg(
+            long,
+            value_name = "CMD",
+            conflicts_with_all = [
+                "system_prompt",
+                "append_prompt",
+                "mcp_config",
+                "agent_args",
+                "trailing_agent_args",
+                "container"
+            ],
+            help = "Shell command to run in the worktree instead of the agent"
+        )]
+        command: Option<String>,
+        #[arg(
+            last = true,
+            value_name = "AGENT_ARGS",
+            help = "Arguments forwarded to the agent, after any --agent-arg values"
+        )]
+        trailing_agent_args: Vec<String>,
     },
-    #[command(about = "List and select a worktree to switch to")]
-    Ls,
+    #[command(about = "List worktrees")]
+    Ls {
+        #[arg(
+            long,
+            conflicts_with = "branches_only",
+            help = "Print only worktree paths, one per line"
+        )]
+        paths_only: bool,
+        #[arg(long, help = "Print only branch names, one per line")]
+        branches_only: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["paths_only", "branches_only"],
+            help = "Print the registry entries as a JSON array"
+        )]
+        json: bool,
+        #[arg(
+            long,
+            help = "List the registry as is, without checking worktrees against git"
+        )]
+        fast: bool,
+        #[arg(
+            long,
+            conflicts_with = "local",
+            help = "List worktrees of all projects, even inside a git repo"
+        )]
+        global: bool,
+        #[arg(long, help = "List only the current project's worktrees")]
+        local: bool,
+        #[command(flatten)]
+        sort: SortArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    #[command(
+        about = "Pick a worktree interactively and print its path, for cd \"$(maokai switch)\""
+    )]
+    Switch,
     #[command(about = "Remove a worktree")]
     Remove {
-        #[arg(help = "Branch name of the worktree to remove")]
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            help = "Branch name of the worktree to remove"
+        )]
+        branch: Option<String>,
+        #[arg(
+            long,
+            conflicts_with_all = ["branch", "pattern"],
+            help = "ID of the worktree to remove (as shown by `info`), in any project"
+        )]
+        id: Option<String>,
+        #[arg(
+            long,
+            requires = "branch",
+            help = "Only delete the branch and its stale registry entries (no worktree)"
+        )]
+        prune_branch_only: bool,
+        #[arg(
+            long,
+            help = "Force removal even with modified/untracked files or unmerged branches"
+        )]
+        force: bool,
+        #[arg(
+            long,
+            conflicts_with = "branch",
+            help = "Remove every worktree whose branch matches this glob (e.g. 'exp/*')"
+        )]
+        pattern: Option<String>,
+        #[arg(
+            long,
+            requires = "pattern",
+            help = "Treat --pattern as a regular expression"
+        )]
+        regex: bool,
+    },
+    #[command(about = "Open a worktree directory in your editor ($EDITOR, or code if unset)")]
+    Open {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            help = "Branch name (or directory name) of the worktree"
+        )]
+        branch: String,
+    },
+    #[command(about = "Run a command inside a worktree (the command goes after --)")]
+    Exec {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            required_unless_present = "id",
+            help = "Branch name (or directory name) of the worktree"
+        )]
         branch: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "branch",
+            help = "ID of the worktree (as shown by `info`) instead of a branch name"
+        )]
+        id: Option<String>,
+        #[arg(last = true, required = true, help = "Command to run")]
+        command: Vec<String>,
+    },
+    #[command(about = "Print a shell completion script")]
+    Completions {
+        #[arg(value_enum, help = "Shell to generate the script for")]
+        shell: Shell,
     },
     #[command(about = "Show status of all worktrees")]
-    Status,
+    Status {
+        #[command(flatten)]
+        sort: SortArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[arg(long, help = "Print as JSON, including each worktree's live git state")]
+        json: bool,
+        #[arg(long, help = "Only show the worktree with this ID, in any project")]
+        id: Option<String>,
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            num_args = 0..=1,
+            default_missing_value = "2",
+            require_equals = true,
+            value_parser = clap::value_parser!(u64).range(1..),
+            conflicts_with = "json",
+            help = "Redraw every SECONDS (default 2), with git state, until Ctrl-C"
+        )]
+        watch: Option<u64>,
+    },
     #[command(about = "Get path for a specific worktree by branch name")]
     Path {
-        #[arg(help = "Branch name of the worktree")]
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            required_unless_present = "id",
+            help = "Branch name (or directory name) of the worktree"
+        )]
+        branch: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "branch",
+            help = "ID of the worktree (as shown by `info`) instead of a branch name"
+        )]
+        id: Option<String>,
+    },
+    #[command(
+        about = "Print a cd command for a worktree, for use with eval \"$(maokai cd <branch>)\""
+    )]
+    Cd {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            help = "Branch name (or directory name) of the worktree"
+        )]
         branch: String,
     },
+    #[command(about = "Print shell commands that export a worktree's MAOKAI_* variables")]
+    Env {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            required_unless_present = "id",
+            help = "Branch name (or directory name) of the worktree"
+        )]
+        branch: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "branch",
+            help = "ID of the worktree (as shown by `info`) instead of a branch name"
+        )]
+        id: Option<String>,
+        #[arg(long, value_enum, default_value_t = ShellFormat::Sh, help = "Shell syntax")]
+        format: ShellFormat,
+    },
+    #[command(about = "Rename a worktree's branch, keeping the worktree and its changes")]
+    Rename {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            help = "Current branch name (or directory name) of the worktree"
+        )]
+        old_branch: String,
+        #[arg(help = "New branch name")]
+        new_branch: String,
+    },
+    #[command(about = "Lock a worktree so it isn't removed without --force")]
+    Lock {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            help = "Branch name (or directory name) of the worktree"
+        )]
+        branch: String,
+        #[arg(help = "Why the worktree is locked, shown by status and info")]
+        reason: Option<String>,
+    },
+    #[command(about = "Unlock a worktree locked with `lock`")]
+    Unlock {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            help = "Branch name (or directory name) of the worktree"
+        )]
+        branch: String,
+    },
+    #[command(about = "Show full details for one worktree")]
+    Info {
+        #[arg(
+            add = ArgValueCandidates::new(worktree_branches),
+            required_unless_present = "id",
+            help = "Branch name (or directory name) of the worktree"
+        )]
+        branch: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "branch",
+            help = "ID of the worktree (as shown by `info`) instead of a branch name"
+        )]
+        id: Option<String>,
+        #[arg(long, help = "Print as JSON")]
+        json: bool,
+    },
+    #[command(about = "List supported agents, whether they are installed, and their capabilities")]
+    Agents {
+        #[arg(long, help = "Print as JSON")]
+        json: bool,
+    },
+    #[command(
+        about = "Remove all maokai worktrees and delete the registry, workspaces and aliases"
+    )]
+    Reset {
+        #[arg(
+            long,
+            help = "Actually reset; without it, only show what would be removed"
+        )]
+        confirm: bool,
+        #[arg(long, help = "Also remove worktrees with uncommitted changes")]
+        force: bool,
+        #[arg(long, help = "Also delete the prompt library (~/maokai-prompts)")]
+        prompts: bool,
+    },
+    #[command(
+        about = "Check that create, list and remove work with this machine's git, in a throwaway repository"
+    )]
+    Selftest,
+    #[command(about = "Import legacy .maokai-info.json files into the registry")]
+    Migrate {
+        #[arg(
+            long,
+            help = "Show what would be imported and deleted without changing anything"
+        )]
+        dry_run: bool,
+    },
+    #[command(about = "Remove registry entries whose worktree is gone from disk and from git")]
+    Prune {
+        #[arg(long, help = "Show what would be removed without changing anything")]
+        dry_run: bool,
+        #[arg(
+            long,
+            help = "Also prune migrated entries that have no project root, when their directory is gone"
+        )]
+        include_migrated: bool,
+    },
+    #[command(about = "Inspect or hand-edit the worktree registry")]
+    Registry {
+        #[command(subcommand)]
+        command: RegistryCommands,
+    },
+    #[command(about = "Work with the system prompt library in $HOME/maokai-prompts")]
+    Prompt {
+        #[command(subcommand)]
+        command: PromptCommands,
+    },
     #[command(about = "Manage workspaces (groups of worktrees across multiple repos)")]
     Workspace {
         #[command(subcommand)]
@@ -53,6 +532,127 @@ pub enum Commands {
     },
 }
 
+/// Ordering flags shared by the listing commands.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct SortArgs {
+    #[arg(long, value_enum, default_value_t = SortBy::Created, help = "Field to sort by")]
+    pub sort: SortBy,
+    #[arg(
+        long,
+        value_enum,
+        help = "Sort direction (defaults to newest first for created and activity, A-Z otherwise)"
+    )]
+    pub order: Option<SortOrder>,
+}
+
+/// Creation-time filters shared by the listing commands.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct FilterArgs {
+    #[arg(
+        long,
+        value_parser = parse_since,
+        help = "Only worktrees created on/after this date (YYYY-MM-DD) or age (e.g. 7d, 12h, 2w)"
+    )]
+    pub since: Option<DateTime<Utc>>,
+    #[arg(
+        long,
+        value_parser = parse_until,
+        help = "Only worktrees created on/before this date (YYYY-MM-DD) or age (e.g. 7d, 12h, 2w)"
+    )]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl FilterArgs {
+    pub fn matches(&self, wt: &WorktreeInfo) -> bool {
+        self.since.is_none_or(|since| wt.created_at >= since)
+            && self.until.is_none_or(|until| wt.created_at <= until)
+    }
+}
+
+/// Branches of the worktrees `ls` would show from the current directory, for dynamic
+/// shell completion. Errors complete to nothing rather than breaking the shell.
+fn worktree_branches() -> Vec<CompletionCandidate> {
+    let Ok(cwd) = std::env::current_dir() else {
+        return Vec::new();
+    };
+    let manager = WorktreeManager::new(cwd, get_worktree_base_path());
+    let worktrees = if manager.is_git_repo() {
+        manager.list_worktrees()
+    } else {
+        manager.list_all_worktrees()
+    };
+    worktrees
+        .unwrap_or_default()
+        .into_iter()
+        .map(|wt| CompletionCandidate::new(wt.branch).help(Some(wt.project_name.into())))
+        .collect()
+}
+
+fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_time_bound(value, false)
+}
+
+fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_time_bound(value, true)
+}
+
+/// Parse a local `YYYY-MM-DD` date or a relative age like `30m`, `12h`, `7d`, `2w`
+/// (meaning that long ago). Dates resolve to the start of the day, or its end when
+/// `end_of_day` is set, so `--until` includes the whole day.
+fn parse_time_bound(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let time = if end_of_day {
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap()
+        } else {
+            NaiveTime::MIN
+        };
+        return date
+            .and_time(time)
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok_or_else(|| format!("invalid local time for date '{}'", value));
+    }
+
+    let expected = || format!("expected YYYY-MM-DD or an age like 7d, got '{}'", value);
+    let mut chars = value.chars();
+    let unit = chars.next_back().ok_or_else(expected)?;
+    let amount: i64 = chars.as_str().parse().map_err(|_| expected())?;
+    let age = match unit {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => {
+            return Err(format!(
+                "unknown age unit in '{}' (use m, h, d or w)",
+                value
+            ));
+        }
+    };
+    age.and_then(|age| Utc::now().checked_sub_signed(age))
+        .ok_or_else(|| format!("age '{}' is out of range", value))
+}
+
+#[derive(Subcommand)]
+pub enum RegistryCommands {
+    #[command(about = "Print the registry file location")]
+    Path,
+    #[command(about = "Open the registry in $EDITOR, rejecting invalid JSON")]
+    Edit,
+}
+
+#[derive(Subcommand)]
+pub enum PromptCommands {
+    #[command(about = "Show a unified diff between two prompts")]
+    Diff {
+        #[arg(help = "Prompt to diff from (name, with or without .md)")]
+        a: String,
+        #[arg(help = "Prompt to diff to")]
+        b: String,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum WorkspaceCommands {
     #[command(about = "List all workspaces")]
@@ -63,6 +663,29 @@ pub enum WorkspaceCommands {
         name: String,
         #[arg(long, help = "Alias to use for project list")]
         alias: Option<String>,
+        #[arg(
+            long,
+            short = 'j',
+            help = "Maximum number of worktrees to create in parallel (defaults to CPU count)"
+        )]
+        jobs: Option<usize>,
+        #[arg(
+            long,
+            help = "If any project fails, roll back the worktrees already created"
+        )]
+        atomic: bool,
+    },
+    #[command(about = "Run a command in every worktree of a workspace")]
+    Exec {
+        #[arg(help = "Workspace name")]
+        name: String,
+        #[arg(
+            long,
+            help = "Run in all worktrees at once, printing output per project"
+        )]
+        parallel: bool,
+        #[arg(last = true, required = true, help = "Command to run")]
+        command: Vec<String>,
     },
     #[command(about = "Remove a workspace", alias = "rm")]
     Remove {
@@ -94,17 +717,147 @@ pub enum AliasCommands {
     Ls,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OpenAfter {
+    Editor,
+    Shell,
+}
+
 #[derive(ValueEnum, Clone, Debug)]
 pub enum Agents {
     Claude,
     Gemini,
+    Aider,
+    /// First installed agent, in the order claude, gemini, aider
+    Auto,
 }
 
-impl ToString for Agents {
-    fn to_string(&self) -> String {
+impl fmt::Display for Agents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Agents::Claude => "claude".to_string(),
-            Agents::Gemini => "gemini".to_string(),
+            Agents::Claude => write!(f, "claude"),
+            Agents::Gemini => write!(f, "gemini"),
+            Agents::Aider => write!(f, "aider"),
+            Agents::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("maokai").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn relative_does_not_take_the_next_word() {
+        let cli = parse(&["--relative", "ls"]);
+        assert_eq!(cli.relative, Some(RelativeTo::Home));
+        assert!(matches!(cli.command, Some(Commands::Ls { .. })));
+
+        let cli = parse(&["path", "--relative", "foo"]);
+        assert_eq!(cli.relative, Some(RelativeTo::Home));
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Path { branch: Some(ref b), .. }) if b == "foo"
+        ));
+
+        let cli = parse(&["path", "foo", "--relative=cwd"]);
+        assert_eq!(cli.relative, Some(RelativeTo::Cwd));
+    }
+
+    #[test]
+    fn watch_does_not_take_the_next_word() {
+        let Some(Commands::Status { watch, .. }) = parse(&["status", "--watch"]).command else {
+            panic!("expected status");
+        };
+        assert_eq!(watch, Some(2));
+
+        let Some(Commands::Status { watch, .. }) = parse(&["status", "--watch=5"]).command else {
+            panic!("expected status");
+        };
+        assert_eq!(watch, Some(5));
+
+        // `ls` is a stray argument, not a bad interval
+        let Err(err) = Cli::try_parse_from(["maokai", "status", "--watch", "ls"]) else {
+            panic!("expected an error");
+        };
+        assert_eq!(err.kind(), clap::error::ErrorKind::UnknownArgument);
+    }
+
+    #[test]
+    fn remote_does_not_take_the_branch() {
+        let Some(Commands::Create { branch, remote, .. }) =
+            parse(&["create", "--push", "--remote", "feature-x"]).command
+        else {
+            panic!("expected create");
+        };
+        assert_eq!(branch.as_deref(), Some("feature-x"));
+        assert_eq!(remote.as_deref(), Some("origin"));
+
+        let Some(Commands::Create { branch, remote, .. }) =
+            parse(&["create", "--remote=upstream", "feature-x"]).command
+        else {
+            panic!("expected create");
+        };
+        assert_eq!(branch.as_deref(), Some("feature-x"));
+        assert_eq!(remote.as_deref(), Some("upstream"));
+    }
+
+    #[test]
+    fn time_bound_relative_ages() {
+        let before = Utc::now();
+        for (value, age) in [
+            ("30m", Duration::minutes(30)),
+            ("12h", Duration::hours(12)),
+            ("7d", Duration::days(7)),
+            ("2w", Duration::weeks(2)),
+        ] {
+            let bound = parse_time_bound(value, false).unwrap();
+            let after = Utc::now();
+            assert!(bound >= before - age && bound <= after - age, "{}", value);
+        }
+    }
+
+    #[test]
+    fn time_bound_dates_cover_the_whole_day() {
+        let start = parse_time_bound("2024-03-05", false).unwrap();
+        let end = parse_time_bound("2024-03-05", true).unwrap();
+        assert_eq!(
+            start.with_timezone(&Local).naive_local(),
+            NaiveDate::from_ymd_opt(2024, 3, 5)
+                .unwrap()
+                .and_time(NaiveTime::MIN)
+        );
+        assert_eq!(end - start, Duration::seconds(24 * 60 * 60 - 1));
+    }
+
+    #[test]
+    fn time_bound_errors() {
+        for value in [
+            "",
+            "d",
+            "7",
+            "7x",
+            "7ä",
+            "ä",
+            "seven d",
+            "2024-13-01",
+            "99999999999999w",
+        ] {
+            assert!(parse_time_bound(value, false).is_err(), "{:?}", value);
         }
+        assert!(
+            parse_time_bound("7x", false)
+                .unwrap_err()
+                .contains("unknown age unit")
+        );
+        assert!(
+            parse_time_bound("99999999999999w", false)
+                .unwrap_err()
+                .contains("out of range")
+        );
     }
 }
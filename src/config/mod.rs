@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 pub fn base_dir() -> PathBuf {
     let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
@@ -13,14 +16,288 @@ pub fn alias_dir() -> PathBuf {
     base_dir().join("alias")
 }
 
+/// Point HOME at a temporary directory shared by every test in the binary, so tests
+/// never touch the real `~/.maokai` or prompt library. Returns that directory.
+#[cfg(test)]
+pub(crate) fn test_home() -> &'static Path {
+    static HOME: std::sync::OnceLock<tempfile::TempDir> = std::sync::OnceLock::new();
+    HOME.get_or_init(|| {
+        let home = tempfile::tempdir().unwrap();
+        // SAFETY: the environment is only written here, once, and tests that read HOME
+        // call this first.
+        unsafe {
+            std::env::set_var("HOME", home.path());
+            std::env::set_var(crate::worktree::NO_AUTO_MIGRATE_ENV, "1");
+        }
+        home
+    })
+    .path()
+}
+
+/// Expand a leading `~` and `$VAR`/`${VAR}` references in a user-supplied path. Unset
+/// variables are left as written, so error messages show what was configured.
+pub fn expand_path(path: &Path) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path.to_path_buf();
+    };
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    if let Some(home) = dirs::home_dir()
+        && (rest == "~" || rest.starts_with("~/"))
+    {
+        expanded.push_str(&home.to_string_lossy());
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(&rest[start..start + 1 + len]),
+        }
+        rest = &after[len..];
+    }
+    expanded.push_str(rest);
+    PathBuf::from(expanded)
+}
+
 pub fn get_worktree_base_path() -> PathBuf {
     if let Ok(path) = std::env::var("MAOKAI_WORKTREE_PATH") {
-        PathBuf::from(path)
+        expand_path(Path::new(&path))
     } else {
         base_dir().join("worktrees")
     }
 }
 
+/// The git executable: `MAOKAI_GIT`, then `git_path` from the user config, then `git`
+/// looked up on PATH.
+pub fn git_program() -> PathBuf {
+    if let Some(path) = std::env::var_os("MAOKAI_GIT").filter(|path| !path.is_empty()) {
+        return expand_path(Path::new(&path));
+    }
+    load_config()
+        .ok()
+        .and_then(|config| config.git_path)
+        .unwrap_or_else(|| PathBuf::from("git"))
+}
+
 pub fn worktrees_registry_path() -> PathBuf {
     base_dir().join("worktrees.json")
 }
+
+pub fn config_path() -> PathBuf {
+    base_dir().join("config.toml")
+}
+
+/// How a project's name is derived from its root path. The name prefixes worktree
+/// directories and is shown in listings, so two projects must not share one.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectNameStrategy {
+    /// Always use the directory name, even if another project already uses it
+    Basename,
+    /// Prefix the parent directory name on collision (`client-api`)
+    #[default]
+    Parent,
+    /// Append a short hash of the full path on collision (`api-1a2b3c`)
+    Hash,
+}
+
+/// User configuration read from `~/.maokai/config.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub project_name_strategy: ProjectNameStrategy,
+    pub default_agent: Option<String>,
+    /// Directory whose contents seed every new worktree (overridden by `--template`)
+    pub template: Option<PathBuf>,
+    /// Also write a `.maokai-info.json` into each worktree, for tooling that predates
+    /// the central registry
+    pub emit_worktree_info: bool,
+    /// Group worktrees into a directory per agent (`<base>/<agent>/<name>`)
+    pub worktrees_per_agent: bool,
+    /// Minimum CLI version per agent, e.g. `{ claude = "1.2.0" }`
+    pub agent_min_version: BTreeMap<String, String>,
+    /// Bulk removals of more worktrees than this require `--yes`
+    pub confirm_remove_threshold: Option<usize>,
+    /// Per-agent settings, from `[agent.<name>]` tables
+    pub agent: BTreeMap<String, AgentConfig>,
+    /// Shell command that exits 0 once a new worktree is ready (see `create --wait-ready`)
+    pub ready_check: Option<String>,
+    /// Also copy `.env*` files from directories between the git root and the project root
+    pub copy_parent_env: bool,
+    /// Issue URL recorded by `create --from-issue`, with `{number}` replaced
+    pub issue_url_template: Option<String>,
+    /// Git executable to run instead of `git` from PATH (overridden by `MAOKAI_GIT`)
+    pub git_path: Option<PathBuf>,
+    /// Prefix of branch names generated when `create` is given none
+    pub scratch_branch_prefix: Option<String>,
+    /// Shell command run in the project root after a worktree is removed
+    pub post_remove: Option<String>,
+    /// Fail the removal command when `post_remove` fails, instead of warning
+    pub post_remove_strict: bool,
+    /// `create` and `ls` warn when more worktrees than this are active (0 disables)
+    pub warn_worktree_count: Option<usize>,
+    /// Project roots whose `.maokai.toml` may set commands for maokai to run
+    pub trusted_repos: Vec<PathBuf>,
+}
+
+/// Settings for one agent, shared by user and repo config.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// Arguments passed to the agent before any `--agent-arg`
+    pub args: Vec<String>,
+    /// Executable to run instead of the agent's default command. Only read from the
+    /// user config, so a cloned repository can't choose what runs.
+    pub command: Option<PathBuf>,
+}
+
+impl Config {
+    pub const DEFAULT_CONFIRM_REMOVE_THRESHOLD: usize = 3;
+    pub const DEFAULT_SCRATCH_BRANCH_PREFIX: &str = "scratch/";
+    pub const DEFAULT_WARN_WORKTREE_COUNT: usize = 20;
+
+    pub fn confirm_remove_threshold(&self) -> usize {
+        self.confirm_remove_threshold
+            .unwrap_or(Self::DEFAULT_CONFIRM_REMOVE_THRESHOLD)
+    }
+
+    pub fn warn_worktree_count(&self) -> usize {
+        self.warn_worktree_count
+            .unwrap_or(Self::DEFAULT_WARN_WORKTREE_COUNT)
+    }
+
+    /// A branch name for a worktree created without one: the prefix followed by the
+    /// local time, e.g. `scratch/20240501-153012`.
+    pub fn scratch_branch_name(&self) -> String {
+        format!(
+            "{}{}",
+            self.scratch_branch_prefix
+                .as_deref()
+                .unwrap_or(Self::DEFAULT_SCRATCH_BRANCH_PREFIX),
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        )
+    }
+}
+
+/// Repository configuration read from `.maokai.toml` at the project root. Meant to be
+/// committed so a team shares the same settings; takes precedence over user config.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RepoConfig {
+    pub default_agent: Option<String>,
+    pub agent_min_version: BTreeMap<String, String>,
+    pub agent: BTreeMap<String, AgentConfig>,
+    pub ready_check: Option<String>,
+    pub copy_parent_env: Option<bool>,
+    pub issue_url_template: Option<String>,
+    pub post_remove: Option<String>,
+    pub post_remove_strict: Option<bool>,
+    /// Directory this project's worktrees are created in, instead of the global base;
+    /// relative paths are resolved against the project root
+    pub worktree_base: Option<PathBuf>,
+    /// Untracked files and directories, relative to the project root, copied into each
+    /// new worktree (e.g. `.vscode/settings.json`, `config.local.yml`)
+    pub copy: Vec<PathBuf>,
+}
+
+/// The executable configured for `agent` in the user config, if any.
+pub fn configured_agent_command(agent: &str) -> Result<Option<PathBuf>> {
+    Ok(load_config()?
+        .agent
+        .remove(agent)
+        .and_then(|agent_config| agent_config.command))
+}
+
+/// Configured arguments for `agent`: the user config's first, then the repo's.
+pub fn configured_agent_args(
+    config: &Config,
+    repo_config: &RepoConfig,
+    agent: &str,
+) -> Vec<String> {
+    [&config.agent, &repo_config.agent]
+        .into_iter()
+        .filter_map(|agents| agents.get(agent))
+        .flat_map(|agent_config| agent_config.args.iter().cloned())
+        .collect()
+}
+
+pub fn load_config() -> Result<Config> {
+    let path = config_path();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let mut config: Config = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    config.template = config.template.as_deref().map(expand_path);
+    config.git_path = config.git_path.as_deref().map(expand_path);
+    for agent in config.agent.values_mut() {
+        agent.command = agent.command.as_deref().map(expand_path);
+    }
+    for repo in &mut config.trusted_repos {
+        *repo = expand_path(repo);
+    }
+    Ok(config)
+}
+
+/// Pass through `command`, the value of `key` in the `.maokai.toml` of `project_root`,
+/// only if the user config lists the project in `trusted_repos`. Otherwise it is
+/// dropped with a warning, so a cloned repository can't choose what runs.
+pub fn trusted_repo_command(
+    config: &Config,
+    project_root: &Path,
+    key: &str,
+    command: Option<String>,
+) -> Option<String> {
+    let command = command?;
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let root = canonical(project_root);
+    if config
+        .trusted_repos
+        .iter()
+        .any(|repo| canonical(repo) == root)
+    {
+        return Some(command);
+    }
+    eprintln!(
+        "Warning: ignoring {} from {}; add the project to trusted_repos in {} to allow it",
+        key,
+        repo_config_path(project_root).display(),
+        config_path().display()
+    );
+    None
+}
+
+pub fn repo_config_path(project_root: &Path) -> PathBuf {
+    project_root.join(".maokai.toml")
+}
+
+pub fn load_repo_config(project_root: &Path) -> Result<RepoConfig> {
+    let path = repo_config_path(project_root);
+    if !path.exists() {
+        return Ok(RepoConfig::default());
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read repo config: {}", path.display()))?;
+    toml::from_str(&content)
+        .with_context(|| format!("Failed to parse repo config: {}", path.display()))
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the file that lifecycle events are appended to.
+pub const EVENTS_ENV: &str = "MAOKAI_EVENTS";
+
+/// A lifecycle event, written as one JSON object per line.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    WorktreeCreated {
+        id: &'a str,
+        branch: &'a str,
+        path: &'a Path,
+        project_root: &'a Path,
+    },
+    WorktreeRemoved {
+        branch: &'a str,
+        path: &'a Path,
+    },
+    AgentStarted {
+        worktree_id: &'a str,
+        branch: &'a str,
+        agent: &'a str,
+    },
+    AgentExited {
+        worktree_id: &'a str,
+        branch: &'a str,
+        agent: &'a str,
+        success: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+fn events_path() -> Option<PathBuf> {
+    std::env::var_os(EVENTS_ENV)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Append `event` to the file named by `MAOKAI_EVENTS`, if set. Observers are best
+/// effort: a failure to write is reported but never fails the command.
+pub fn emit(event: Event<'_>) {
+    let Some(path) = events_path() else {
+        return;
+    };
+
+    let record = Record {
+        timestamp: Utc::now(),
+        event: &event,
+    };
+
+    let result = serde_json::to_string(&record)
+        .map_err(std::io::Error::from)
+        .and_then(|line| {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            writeln!(file, "{}", line)
+        });
+
+    if let Err(e) = result {
+        eprintln!(
+            "Warning: failed to write event to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Command;
+
+/// Longest slug taken from an issue title, so branch and directory names stay readable.
+const MAX_SLUG_LEN: usize = 40;
+
+/// An issue a worktree is created for.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub number: u64,
+    /// None when the title could not be fetched
+    pub title: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Issue {
+    /// Branch name for the issue: its number followed by a slug of its title, e.g.
+    /// `123-fix-login-crash`, or `issue-123` when the title is unknown.
+    pub fn branch_name(&self) -> String {
+        let slug = self.title.as_deref().map(slugify).unwrap_or_default();
+        if slug.is_empty() {
+            format!("issue-{}", self.number)
+        } else {
+            format!("{}-{}", self.number, slug)
+        }
+    }
+
+    /// What the registry records for the issue: its URL, or `#<number>` without one.
+    pub fn reference(&self) -> String {
+        self.url
+            .clone()
+            .unwrap_or_else(|| format!("#{}", self.number))
+    }
+}
+
+#[derive(Deserialize)]
+struct GhIssue {
+    title: String,
+    url: String,
+}
+
+/// Look up issue `number` with the GitHub CLI, run in `project_root` so `gh` picks the
+/// repository from its remotes. A configured `url_template` (with `{number}`) replaces
+/// the GitHub URL; when `gh` is not installed it is the only source, and the title is
+/// left unknown.
+pub fn fetch(project_root: &Path, number: u64, url_template: Option<&str>) -> Result<Issue> {
+    let template_url =
+        url_template.map(|template| template.replace("{number}", &number.to_string()));
+
+    let output = match Command::new("gh")
+        .args(["issue", "view", &number.to_string(), "--json", "title,url"])
+        .current_dir(project_root)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && template_url.is_some() => {
+            eprintln!(
+                "Warning: gh is not installed; naming the branch after the issue number only"
+            );
+            return Ok(Issue {
+                number,
+                title: None,
+                url: template_url,
+            });
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!(
+                "--from-issue needs the GitHub CLI (gh) or an issue_url_template in the config"
+            );
+        }
+        Err(e) => return Err(e).context("Failed to run gh"),
+    };
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to fetch issue #{}: {}",
+            number,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let issue: GhIssue = serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Failed to parse gh output for issue #{}", number))?;
+    Ok(Issue {
+        number,
+        title: Some(issue.title),
+        url: Some(template_url.unwrap_or(issue.url)),
+    })
+}
+
+/// Lowercase `title`, keep ASCII letters and digits and join the words with `-`,
+/// cutting at a word boundary once it reaches [`MAX_SLUG_LEN`].
+fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for word in title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let extra = word.len() + usize::from(!slug.is_empty());
+        if !slug.is_empty() && slug.len() + extra > MAX_SLUG_LEN {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug.truncate(MAX_SLUG_LEN);
+    slug
+}
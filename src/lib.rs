@@ -1,7 +1,13 @@
 pub mod agent;
 pub mod cli;
 pub mod config;
+pub mod events;
+pub mod issue;
+pub mod ops;
+pub mod output;
 pub mod prompt;
+pub mod selftest;
+pub mod ui;
 pub mod workspace;
 pub mod worktree;
 
@@ -1,18 +1,52 @@
 use anyhow::Result;
-use clap::Parser;
+use chrono::Local;
+use clap::{CommandFactory, Parser};
+use clap_complete::env::CompleteEnv;
+use std::collections::BTreeMap;
 use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
 use std::process::{Command, Stdio};
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
 
-use maokai::agent::get_agent;
-use maokai::cli::{AliasCommands, Commands, WorkspaceCommands};
-use maokai::config::get_worktree_base_path;
+use maokai::agent::{ContainerAgent, PromptMode, describe_agents, get_agent, resolve_auto};
+use maokai::cli::{
+    Agents, AliasCommands, Commands, OpenAfter, PromptCommands, RegistryCommands, WorkspaceCommands,
+};
+use maokai::config::{
+    configured_agent_args, get_worktree_base_path, load_config, load_repo_config,
+    trusted_repo_command, worktrees_registry_path,
+};
+use maokai::ops::{self, CreateRequest, LaunchOptions, ReadyCheck};
 use maokai::workspace::alias::AliasManager;
-use maokai::workspace::WorkspaceManager;
-use maokai::{Cli, WorktreeManager};
+use maokai::workspace::editor::open_in_editor;
+use maokai::workspace::{WorkspaceManager, default_jobs};
+use maokai::worktree::{
+    CreateOptions, WorktreeInfo, WorktreeInfoReport, WorktreeStatus, WorktreeStatusReport,
+    disk_usage, sort_worktrees,
+};
+use maokai::{Cli, PromptManager, WorktreeManager, issue, output, selftest, ui};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Answers the shell when sourced as `COMPLETE=<shell> maokai`, then exits
+    CompleteEnv::with_factory(Cli::command).complete();
     let cli = Cli::parse();
+    init_logging(cli.verbose, !cli.no_color && !ui::is_plain());
+
+    if cli.plain {
+        ui::set_plain();
+    }
+    if cli.yes {
+        ui::set_assume_yes();
+    }
+    if cli.no_color || ui::is_plain() {
+        output::disable_color();
+    }
+    if let Some(base) = cli.relative {
+        output::set_relative_paths(base);
+    }
 
     let project_root = env::current_dir()?;
     let worktree_base_path = get_worktree_base_path();
@@ -21,58 +55,259 @@ async fn main() -> Result<()> {
     match cli.command {
         Some(Commands::Create {
             branch,
+            stdin,
+            no_agent,
             agent,
             system_prompt,
+            append_prompt,
+            mcp_config,
             base_branch,
-            custom_command,
+            remote,
+            fetch,
+            move_changes,
+            snapshot_changes,
+            rename_on_conflict,
+            path,
+            attach_if_exists,
+            base_path_per_agent,
+            copy_ignored,
+            initial_commit,
+            push,
+            require_push,
+            wait_ready,
+            timeout,
+            template,
+            force,
+            container,
+            open_after,
+            complete_on_exit,
+            agent_args,
+            agent_env,
+            command,
+            trailing_agent_args,
+            from_issue,
         }) => {
-            let worktree_info = worktree_manager.create_worktree(
-                &branch,
-                &agent.to_string(),
-                base_branch.as_deref(),
-            )?;
-            // Print path for directory change (always output the path)
-            println!("{}", worktree_info.path.display());
+            let agent = resolve_agent(agent, &project_root)?;
+            let agent = if no_agent || stdin || command.is_some() {
+                // The agent is only a label here, so `auto` need not find an installed one
+                resolve_auto(&agent).unwrap_or_else(|_| Agents::Claude.to_string())
+            } else {
+                resolve_auto(&agent)?
+            };
+            let agent_env = parse_agent_env(&agent_env)?;
+            // The agent runs inside the worktree, so relative paths must be resolved here
+            let mcp_config = mcp_config
+                .map(|path| {
+                    path.canonicalize().map_err(|e| {
+                        anyhow::anyhow!("MCP config '{}' not found: {}", path.display(), e)
+                    })
+                })
+                .transpose()?;
+            // Validate the agent before creating anything, since config values are free-form
+            let agent_impl = get_agent(&agent)?;
+            if let Some(image) = &container {
+                ContainerAgent::new(agent_impl, image)?;
+            }
+            let launch_agent = !(no_agent || stdin);
+            // A containerized agent is whatever version the image ships
+            if launch_agent && command.is_none() && container.is_none() {
+                ops::check_agent_version(&agent, &project_root)?;
+            }
 
-            if !custom_command.is_empty() {
-                // Run custom command
-                let (cmd_name, cmd_args) = custom_command.split_first().unwrap();
-                let mut cmd = Command::new(cmd_name);
-                cmd.args(cmd_args);
-                cmd.current_dir(&worktree_info.path);
+            let config = load_config()?;
+            let repo_config = load_repo_config(&project_root)?;
+            let wait_ready = if wait_ready {
+                let repo_command = trusted_repo_command(
+                    &config,
+                    &project_root,
+                    "ready_check",
+                    repo_config.ready_check.clone(),
+                );
+                let Some(command) = repo_command.or(config.ready_check.clone()) else {
+                    anyhow::bail!(
+                        "--wait-ready needs a ready_check command in .maokai.toml or ~/.maokai/config.toml"
+                    );
+                };
+                Some(ReadyCheck {
+                    command,
+                    timeout: Duration::from_secs(timeout),
+                })
+            } else {
+                None
+            };
+            // Later arguments win for agents that let a repeated flag override an earlier one
+            let agent_args = [
+                configured_agent_args(&config, &repo_config, &agent),
+                agent_args,
+                trailing_agent_args,
+            ]
+            .concat();
+            let mut request = CreateRequest {
+                agent,
+                move_changes,
+                snapshot_changes,
+                template: template.or_else(|| config.template.clone()),
+                overwrite_template: force,
+                copy_ignored,
+                attach_if_exists,
+                push: (push || require_push)
+                    .then(|| remote.clone().unwrap_or_else(|| "origin".to_string())),
+                require_push,
+                wait_ready,
+                options: CreateOptions {
+                    base_branch,
+                    remote,
+                    fetch,
+                    rename_on_conflict,
+                    per_agent_dir: base_path_per_agent || config.worktrees_per_agent,
+                    path,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let initial_commit_for = |branch: &str| {
+                initial_commit
+                    .clone()
+                    .map(|message| message.unwrap_or_else(|| format!("Start {}", branch)))
+            };
 
-                // Set environment variables with worktree info
-                cmd.env("MAOKAI_WORKTREE_PATH", &worktree_info.path);
-                cmd.env("MAOKAI_BRANCH", &worktree_info.branch);
-                cmd.env("MAOKAI_AGENT", &worktree_info.agent);
-                cmd.env("MAOKAI_PROJECT_NAME", &worktree_info.project_name);
-                cmd.env("MAOKAI_WORKTREE_ID", &worktree_info.id);
+            if stdin {
+                let mut total = 0;
+                let mut failed = 0;
+                for line in io::stdin().lines() {
+                    let line = line?;
+                    let branch = line.trim();
+                    if branch.is_empty() {
+                        continue;
+                    }
+                    total += 1;
 
-                cmd.stdin(Stdio::inherit());
-                cmd.stdout(Stdio::inherit());
-                cmd.stderr(Stdio::inherit());
+                    request.branch = branch.to_string();
+                    request.options.initial_commit = initial_commit_for(branch);
+                    match ops::create(&worktree_manager, &request) {
+                        Ok(info) => println!("{}", output::display_path(&info.path)),
+                        Err(e) => {
+                            eprintln!("Failed to create worktree for '{}': {:#}", branch, e);
+                            failed += 1;
+                        }
+                    }
+                }
 
-                let status = cmd.status().map_err(|e| {
-                    anyhow::anyhow!("Failed to execute custom command '{}': {}", cmd_name, e)
-                })?;
+                if failed > 0 {
+                    eprintln!("{} of {} worktrees failed", failed, total);
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
 
-                if !status.success() {
-                    anyhow::bail!("Custom command failed with exit code: {:?}", status.code());
+            let branch = match from_issue {
+                Some(number) => {
+                    let issue = issue::fetch(
+                        &project_root,
+                        number,
+                        repo_config
+                            .issue_url_template
+                            .as_deref()
+                            .or(config.issue_url_template.as_deref()),
+                    )?;
+                    request.options.issue = Some(issue.reference());
+                    issue.branch_name()
                 }
-            } else {
-                // Use default agent behavior
-                let agent_impl = get_agent(&agent.to_string())?;
-                agent_impl.start(&worktree_info, system_prompt.as_deref(), &[])?;
+                None => branch.unwrap_or_else(|| {
+                    let generated = config.scratch_branch_name();
+                    eprintln!("Using generated branch name '{}'", generated);
+                    generated
+                }),
+            };
+            request.options.initial_commit = initial_commit_for(&branch);
+            request.branch = branch;
+            let worktree_info = ops::create(&worktree_manager, &request)?;
+            ops::warn_on_worktree_count(
+                &worktree_manager.list_registered_worktrees()?,
+                "in this project",
+            )?;
+
+            // Print path for directory change (always output the path)
+            println!("{}", output::display_path(&worktree_info.path));
+
+            if launch_agent {
+                let launch_options = LaunchOptions {
+                    system_prompt,
+                    prompt_mode: if append_prompt {
+                        PromptMode::Append
+                    } else {
+                        PromptMode::Replace
+                    },
+                    mcp_config,
+                    agent_env,
+                    agent_args,
+                    command,
+                    container,
+                };
+                let result = ops::launch(&worktree_info, &launch_options);
+                // A failed run leaves the worktree Active so it is easy to resume
+                if complete_on_exit && result.is_ok() {
+                    worktree_manager.set_status(&worktree_info.id, WorktreeStatus::Completed)?;
+                    eprintln!("Marked worktree '{}' as completed", worktree_info.branch);
+                }
+                result?;
+            }
+
+            if let Some(mode) = open_after {
+                open_worktree_after(mode, &worktree_info.path)?;
             }
         }
-        Some(Commands::Ls) => {
-            let worktrees = if worktree_manager.is_git_repo() {
-                // Inside a git repo - show project-specific worktrees
-                worktree_manager.list_worktrees()?
+        Some(Commands::Ls {
+            paths_only,
+            branches_only,
+            json,
+            fast,
+            global,
+            local,
+            sort,
+            filter,
+        }) => {
+            // Without a selector: the current project inside a git repo, otherwise all
+            let global = global || (!local && !worktree_manager.is_git_repo());
+            if !global && !worktree_manager.is_git_repo() {
+                anyhow::bail!("--local must be run inside a git repository");
+            }
+
+            let mut worktrees = if global {
+                worktree_manager.list_all_worktrees_sorted(sort.sort, sort.order)?
+            } else if fast {
+                let mut worktrees = worktree_manager.list_registered_worktrees()?;
+                sort_worktrees(&mut worktrees, sort.sort, sort.order);
+                worktrees
             } else {
-                // Outside git repo - show all worktrees from all projects
-                worktree_manager.list_all_worktrees()?
+                worktree_manager.list_worktrees_sorted(sort.sort, sort.order)?
             };
+            if !(paths_only || branches_only || json) {
+                let scope = if global {
+                    "in total"
+                } else {
+                    "in this project"
+                };
+                ops::warn_on_worktree_count(&worktrees, scope)?;
+            }
+            worktrees.retain(|wt| filter.matches(wt));
+
+            if json {
+                // Like the scripting modes, an empty list is `[]`, not an error
+                output::print_json(&worktrees)?;
+                return Ok(());
+            }
+            if paths_only || branches_only {
+                // Scripting modes: an empty list is just empty output, not an error
+                for wt in worktrees {
+                    if paths_only {
+                        println!("{}", output::display_path(&wt.path));
+                    } else {
+                        println!("{}", wt.branch);
+                    }
+                }
+                return Ok(());
+            }
 
             if worktrees.is_empty() {
                 eprintln!("No active worktrees found.");
@@ -80,14 +315,138 @@ async fn main() -> Result<()> {
             }
 
             for wt in worktrees {
-                println!("{} - {} ({})", wt.project_name, wt.branch, wt.agent);
+                output::print_worktree_line(&wt);
             }
         }
-        Some(Commands::Remove { branch }) => match branch {
+        Some(Commands::Remove {
+            branch,
+            id: Some(id),
+            force,
+            ..
+        }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, branch, Some(id))?;
+            let prompt = format!(
+                "Remove worktree {} and delete branch '{}'?",
+                output::display_path(&wt.path),
+                wt.branch
+            );
+            if !ui::confirm(&prompt)? {
+                eprintln!("Aborted.");
+                return Ok(());
+            }
+
+            // The worktree may belong to another project, so run git from its own root
+            WorktreeManager::new(wt.project_root.clone(), worktree_base_path.clone())
+                .remove_worktree_at_path(&wt.path, &wt.branch, force)?;
+            println!("Removed worktree for branch '{}'", wt.branch);
+        }
+        Some(Commands::Remove {
+            branch,
+            prune_branch_only,
+            force,
+            pattern,
+            regex,
+            id: None,
+        }) => match branch {
+            Some(branch_name) if prune_branch_only => {
+                let prompt = format!("Delete branch '{}'?", branch_name);
+                if !ui::confirm(&prompt)? {
+                    eprintln!("Aborted.");
+                    return Ok(());
+                }
+
+                let pruned = worktree_manager.prune_branch(&branch_name, force)?;
+                println!(
+                    "Deleted branch '{}' ({} stale registry entries removed)",
+                    branch_name, pruned
+                );
+            }
             Some(branch_name) => {
-                worktree_manager.remove_worktree(&branch_name)?;
+                let worktrees = if worktree_manager.is_git_repo() {
+                    worktree_manager.list_worktrees()?
+                } else {
+                    worktree_manager.list_all_worktrees()?
+                };
+                if let Some(wt) = worktrees.iter().find(|wt| wt.branch == branch_name) {
+                    let prompt = format!(
+                        "Remove worktree {} and delete branch '{}'?",
+                        output::display_path(&wt.path),
+                        branch_name
+                    );
+                    if !ui::confirm(&prompt)? {
+                        eprintln!("Aborted.");
+                        return Ok(());
+                    }
+                }
+
+                if force {
+                    worktree_manager.remove_worktree_force(&branch_name)?;
+                } else {
+                    worktree_manager.remove_worktree(&branch_name)?;
+                }
                 println!("Removed worktree for branch '{}'", branch_name);
             }
+            None if pattern.is_some() => {
+                let pattern = pattern.unwrap();
+                let matcher = BranchMatcher::new(&pattern, regex)?;
+                let worktrees: Vec<_> = if worktree_manager.is_git_repo() {
+                    worktree_manager.list_worktrees()?
+                } else {
+                    worktree_manager.list_all_worktrees()?
+                }
+                .into_iter()
+                .filter(|wt| matcher.is_match(&wt.branch))
+                .collect();
+
+                if worktrees.is_empty() {
+                    anyhow::bail!("No worktrees match '{}'", pattern);
+                }
+
+                eprintln!("Worktrees matching '{}':", pattern);
+                for wt in &worktrees {
+                    eprintln!("  {} ({})", wt.branch, output::display_path(&wt.path));
+                }
+
+                let threshold = load_config()?.confirm_remove_threshold();
+                if worktrees.len() > threshold && !ui::assume_yes() {
+                    anyhow::bail!(
+                        "Refusing to remove {} worktrees without --yes (more than confirm_remove_threshold = {})",
+                        worktrees.len(),
+                        threshold
+                    );
+                }
+                let prompt = format!(
+                    "Remove these {} worktrees and delete their branches?",
+                    worktrees.len()
+                );
+                if !ui::confirm(&prompt)? {
+                    eprintln!("Aborted.");
+                    return Ok(());
+                }
+
+                let mut failed = 0;
+                for wt in &worktrees {
+                    // Matches may belong to other projects, so run git from each one's own root
+                    let result =
+                        WorktreeManager::new(wt.project_root.clone(), worktree_base_path.clone())
+                            .remove_worktree_at_path(&wt.path, &wt.branch, force);
+                    match result {
+                        Ok(_) => println!("Removed worktree for branch '{}'", wt.branch),
+                        Err(e) => {
+                            eprintln!("Warning: Failed to remove '{}': {}", wt.branch, e);
+                            failed += 1;
+                        }
+                    }
+                }
+
+                if failed > 0 {
+                    anyhow::bail!(
+                        "Failed to remove {} of {} worktrees",
+                        failed,
+                        worktrees.len()
+                    );
+                }
+            }
             _ => {
                 let worktrees = if worktree_manager.is_git_repo() {
                     worktree_manager.list_worktrees()?
@@ -107,37 +466,307 @@ async fn main() -> Result<()> {
                 std::process::exit(1);
             }
         },
-        Some(Commands::Status) => {
-            let worktrees = worktree_manager.list_worktrees()?;
+        Some(Commands::Status {
+            sort,
+            filter,
+            json,
+            id,
+            watch,
+        }) => {
+            let load = || -> Result<Vec<WorktreeInfo>> {
+                let mut worktrees = if let Some(id) = &id {
+                    vec![find_worktree_or_exit(
+                        &worktree_manager,
+                        None,
+                        Some(id.clone()),
+                    )?]
+                } else if worktree_manager.is_git_repo() {
+                    worktree_manager.list_worktrees_sorted(sort.sort, sort.order)?
+                } else {
+                    worktree_manager.list_all_worktrees_sorted(sort.sort, sort.order)?
+                };
+                worktrees.retain(|wt| filter.matches(wt));
+                Ok(worktrees)
+            };
+
+            if let Some(seconds) = watch {
+                let mut interval = tokio::time::interval(Duration::from_secs(seconds));
+                loop {
+                    interval.tick().await;
+                    let worktrees = load()?;
+                    let git_states = worktree_manager.git_summaries(&worktrees)?;
+                    // Clear the screen and move the cursor home before redrawing; when
+                    // piped or plain, successive snapshots are just appended
+                    if io::stdout().is_terminal() && !ui::is_plain() {
+                        print!("\x1b[2J\x1b[H");
+                    }
+                    println!(
+                        "Worktree Status (every {}s, Ctrl-C to quit) {}",
+                        seconds,
+                        Local::now().format("%H:%M:%S")
+                    );
+                    for (wt, git_state) in worktrees.iter().zip(&git_states) {
+                        output::print_worktree_status(wt, git_state.as_ref());
+                    }
+                    io::stdout().flush()?;
+                }
+            }
+
+            let worktrees = load()?;
+            let git_states = worktree_manager.git_summaries(&worktrees)?;
+            if json {
+                let reports: Vec<_> = worktrees
+                    .into_iter()
+                    .zip(git_states)
+                    .map(|(worktree, git_state)| WorktreeStatusReport {
+                        worktree,
+                        git_state,
+                    })
+                    .collect();
+                output::print_json(&reports)?;
+                return Ok(());
+            }
+
             println!("Worktree Status:");
-            for wt in worktrees {
-                println!("  Branch: {}", wt.branch);
-                println!("    Path: {}", wt.path.display());
-                println!("    Agent: {}", wt.agent);
-                println!("    Status: {:?}", wt.status);
-                println!(
-                    "    Created: {}",
-                    wt.created_at.format("%Y-%m-%d %H:%M:%S UTC")
-                );
-                println!();
+            for (wt, git_state) in worktrees.iter().zip(&git_states) {
+                output::print_worktree_status(wt, git_state.as_ref());
+            }
+        }
+        Some(Commands::Path { branch, id }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, branch, id)?;
+            println!("{}", output::display_path(&wt.path));
+        }
+        Some(Commands::Open { branch }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, Some(branch), None)?;
+            open_in_editor(&wt.path)?;
+        }
+        Some(Commands::Exec {
+            branch,
+            id,
+            command,
+        }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, branch, id)?;
+            let code = ops::exec(&wt, &command)?;
+            if code != 0 {
+                std::process::exit(code);
             }
         }
-        Some(Commands::Path { branch }) => {
+        Some(Commands::Completions { shell }) => {
+            clap_complete::generate(shell, &mut Cli::command(), "maokai", &mut io::stdout());
+        }
+        Some(Commands::Switch) => {
             let worktrees = if worktree_manager.is_git_repo() {
                 worktree_manager.list_worktrees()?
             } else {
                 worktree_manager.list_all_worktrees()?
             };
+            if worktrees.is_empty() {
+                eprintln!("No active worktrees found.");
+                std::process::exit(1);
+            }
 
-            for wt in worktrees {
-                if wt.branch == branch {
-                    println!("{}", wt.path.display());
+            // stdout is usually captured by `$(...)`, so the picker needs stdin and stderr.
+            // Without it, stdout may only ever carry a single, unambiguous path.
+            if !ui::is_interactive() {
+                if let [wt] = worktrees.as_slice() {
+                    println!("{}", output::display_path(&wt.path));
                     return Ok(());
                 }
+                eprintln!("Cannot show the picker without a terminal; worktrees:");
+                for wt in &worktrees {
+                    eprintln!(
+                        "  {}: {}",
+                        output::worktree_label(wt),
+                        output::display_path(&wt.path)
+                    );
+                }
+                eprintln!("Use `maokai path <branch>` to pick one non-interactively.");
+                std::process::exit(1);
+            }
+
+            let labels: Vec<String> = worktrees.iter().map(output::worktree_label).collect();
+            match ui::select("Switch to worktree", &labels)? {
+                Some(index) => println!("{}", output::display_path(&worktrees[index].path)),
+                None => std::process::exit(1),
+            }
+        }
+        Some(Commands::Cd { branch }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, Some(branch), None)?;
+            // Always absolute: `~` would not be expanded inside the quotes
+            println!("cd {}", output::sh_quote(&wt.path.to_string_lossy()));
+        }
+        Some(Commands::Env { branch, id, format }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, branch, id)?;
+            for (key, value) in wt.env_vars() {
+                println!("{}", output::format_env_line(format, key, &value));
+            }
+        }
+        Some(Commands::Info { branch, id, json }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, branch, id)?;
+            let git_state = worktree_manager.worktree_git_state(&wt.path)?;
+            let disk_usage = disk_usage(&wt.path);
+
+            if json {
+                let report = WorktreeInfoReport {
+                    worktree: &wt,
+                    git_state: git_state.as_ref(),
+                    disk_usage_bytes: disk_usage,
+                };
+                output::print_json(&report)?;
+            } else {
+                output::print_worktree_info(&wt, git_state.as_ref(), disk_usage);
+            }
+        }
+        Some(Commands::Rename {
+            old_branch,
+            new_branch,
+        }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, Some(old_branch), None)?;
+            let renamed = WorktreeManager::new(wt.project_root.clone(), worktree_base_path.clone())
+                .rename_worktree(&wt, &new_branch)?;
+            eprintln!(
+                "Renamed branch '{}' to '{}' ({})",
+                wt.branch,
+                renamed.branch,
+                output::display_path(&renamed.path)
+            );
+        }
+        Some(Commands::Lock { branch, reason }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, Some(branch), None)?;
+            WorktreeManager::new(wt.project_root.clone(), worktree_base_path.clone())
+                .lock_worktree(&wt.path, reason.as_deref())?;
+            eprintln!("Locked worktree for '{}'", wt.branch);
+        }
+        Some(Commands::Unlock { branch }) => {
+            let wt = find_worktree_or_exit(&worktree_manager, Some(branch), None)?;
+            WorktreeManager::new(wt.project_root.clone(), worktree_base_path.clone())
+                .unlock_worktree(&wt.path)?;
+            eprintln!("Unlocked worktree for '{}'", wt.branch);
+        }
+        Some(Commands::Agents { json }) => {
+            let agents = describe_agents()?;
+            if json {
+                output::print_json(&agents)?;
+            } else {
+                for agent in &agents {
+                    output::print_agent(agent);
+                }
+            }
+        }
+        Some(Commands::Reset {
+            confirm,
+            force,
+            prompts,
+        }) => {
+            if !confirm {
+                let worktrees = worktree_manager.list_all_worktrees()?;
+                eprintln!("This would remove:");
+                eprintln!("  {} worktrees (and their branches)", worktrees.len());
+                eprintln!("  {} workspaces", WorkspaceManager::new().list()?.len());
+                eprintln!("  {} aliases", AliasManager::new().list()?.len());
+                eprintln!("  the registry {}", worktrees_registry_path().display());
+                if prompts {
+                    eprintln!(
+                        "  the prompt library {}",
+                        PromptManager::new()?.prompts_dir().display()
+                    );
+                }
+                anyhow::bail!("Refusing to reset without --confirm");
+            }
+            ops::reset(&worktree_manager, &worktree_base_path, force, prompts)?;
+            eprintln!("Reset complete.");
+        }
+        Some(Commands::Selftest) => {
+            let results = selftest::run()?;
+            for step in &results {
+                output::print_selftest_step(step);
+            }
+            if results.iter().any(|step| step.error.is_some()) {
+                std::process::exit(1);
+            }
+            eprintln!("All {} steps passed.", results.len());
+        }
+        Some(Commands::Migrate { dry_run: true }) => {
+            let plan = worktree_manager.plan_legacy_migration()?;
+            if plan.entries.is_empty() {
+                eprintln!("No legacy worktree info files to migrate.");
+                return Ok(());
+            }
+            for legacy in &plan.entries {
+                println!(
+                    "Would migrate {} ({})",
+                    legacy.info.branch,
+                    output::display_path(&legacy.info.path)
+                );
+                if plan.delete_info_files {
+                    println!("  and delete {}", output::display_path(&legacy.info_path));
+                }
+            }
+            eprintln!(
+                "Would migrate {} worktrees into the registry (dry run, nothing changed).",
+                plan.entries.len()
+            );
+        }
+        Some(Commands::Migrate { dry_run: false }) => {
+            let migrated = worktree_manager.migrate_legacy_info()?;
+            if migrated.is_empty() {
+                eprintln!("No legacy worktree info files to migrate.");
+            } else {
+                for wt in &migrated {
+                    println!(
+                        "Migrated {} ({})",
+                        wt.branch,
+                        output::display_path(&wt.path)
+                    );
+                }
+                eprintln!("Migrated {} worktrees into the registry.", migrated.len());
+            }
+        }
+        Some(Commands::Prune {
+            dry_run,
+            include_migrated,
+        }) => {
+            let pruned = worktree_manager.prune_registry(dry_run, include_migrated)?;
+            if pruned.is_empty() {
+                eprintln!("No orphaned registry entries.");
+                return Ok(());
+            }
+            let verb = if dry_run { "Would prune" } else { "Pruned" };
+            for wt in &pruned {
+                println!(
+                    "{} {} ({})",
+                    verb,
+                    wt.branch,
+                    output::display_path(&wt.path)
+                );
+            }
+            if dry_run {
+                eprintln!(
+                    "Would prune {} registry entries (dry run, nothing changed).",
+                    pruned.len()
+                );
+            } else {
+                eprintln!("Pruned {} registry entries.", pruned.len());
             }
-            eprintln!("Worktree for branch '{}' not found", branch);
-            std::process::exit(1);
         }
+        Some(Commands::Registry { command }) => match command {
+            RegistryCommands::Path => {
+                println!("{}", worktrees_registry_path().display());
+            }
+            RegistryCommands::Edit => {
+                worktree_manager.edit_registry()?;
+            }
+        },
+        Some(Commands::Prompt { command }) => match command {
+            PromptCommands::Diff { a, b } => {
+                let diff = PromptManager::new()?.diff_prompts(&a, &b)?;
+                if diff.is_empty() {
+                    eprintln!("Prompts '{}' and '{}' are identical.", a, b);
+                } else {
+                    output::print_diff(&diff);
+                }
+            }
+        },
         Some(Commands::Workspace { command }) => {
             let ws_manager = WorkspaceManager::new();
             let alias_manager = AliasManager::new();
@@ -153,17 +782,31 @@ async fn main() -> Result<()> {
                                 .alias
                                 .map(|a| format!(" (alias: {})", a))
                                 .unwrap_or_default();
-                            println!(
-                                "{}{} - {} projects",
-                                ws.name,
-                                alias_info,
-                                ws.projects.len()
-                            );
+                            println!("{}{} - {} projects", ws.name, alias_info, ws.projects.len());
                         }
                     }
                 }
-                WorkspaceCommands::Create { name, alias } => {
-                    ws_manager.create(&name, alias.as_deref())?;
+                WorkspaceCommands::Create {
+                    name,
+                    alias,
+                    jobs,
+                    atomic,
+                } => {
+                    let jobs = jobs.unwrap_or_else(default_jobs);
+                    ws_manager
+                        .create(&name, alias.as_deref(), jobs, atomic)
+                        .await?;
+                }
+                WorkspaceCommands::Exec {
+                    name,
+                    parallel,
+                    command,
+                } => {
+                    let failed = ws_manager.exec(&name, &command, parallel).await?;
+                    if failed > 0 {
+                        eprintln!("{} project(s) failed", failed);
+                        std::process::exit(1);
+                    }
                 }
                 WorkspaceCommands::Remove { name, force } => {
                     ws_manager.remove(&name, force)?;
@@ -204,10 +847,140 @@ async fn main() -> Result<()> {
             }
 
             for wt in worktrees {
-                println!("{} - {} ({})", wt.project_name, wt.branch, wt.agent);
+                output::print_worktree_line(&wt);
             }
         }
     }
 
     Ok(())
 }
+
+/// Pick the agent for `create`: CLI flag, then repo `.maokai.toml`, then user config,
+/// then the built-in default.
+fn resolve_agent(cli_agent: Option<Agents>, project_root: &Path) -> Result<String> {
+    if let Some(agent) = cli_agent {
+        return Ok(agent.to_string());
+    }
+    if let Some(agent) = load_repo_config(project_root)?.default_agent {
+        return Ok(agent);
+    }
+    if let Some(agent) = load_config()?.default_agent {
+        return Ok(agent);
+    }
+    Ok(Agents::Claude.to_string())
+}
+
+/// Send `tracing` output to stderr. `RUST_LOG` wins if set; otherwise `-v` enables debug
+/// and `-vv` trace logging for maokai, and only warnings are shown by default.
+fn init_logging(verbose: u8, color: bool) {
+    let level = match verbose {
+        0 => "warn",
+        1 => "maokai=debug",
+        _ => "maokai=trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(color && io::stderr().is_terminal())
+        .without_time()
+        .init();
+}
+
+/// Look a worktree up by ID if given, otherwise by branch (or directory name). Exits
+/// with an error message when nothing matches.
+fn find_worktree_or_exit(
+    manager: &WorktreeManager,
+    branch: Option<String>,
+    id: Option<String>,
+) -> Result<WorktreeInfo> {
+    let found = match (&id, &branch) {
+        (Some(id), _) => manager.find_by_id(id)?,
+        (None, Some(branch)) => manager.find_worktree(branch)?,
+        (None, None) => unreachable!("clap requires a branch or --id"),
+    };
+    if let Some(wt) = found {
+        return Ok(wt);
+    }
+
+    match (id, branch) {
+        (Some(id), _) => eprintln!("Worktree with ID '{}' not found", id),
+        (None, branch) => eprintln!(
+            "Worktree for branch '{}' not found",
+            branch.unwrap_or_default()
+        ),
+    }
+    std::process::exit(1);
+}
+
+/// Post-session step for `create --open-after`: open the worktree in the editor or
+/// drop into an interactive shell there, then remind the user where it lives.
+fn open_worktree_after(mode: OpenAfter, path: &Path) -> Result<()> {
+    match mode {
+        OpenAfter::Editor => open_in_editor(path)?,
+        OpenAfter::Shell => {
+            let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+            eprintln!("Starting {} in {} (exit to return)", shell, path.display());
+            Command::new(&shell)
+                .current_dir(path)
+                .stdin(Stdio::inherit())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .status()
+                .map_err(|e| anyhow::anyhow!("Failed to start shell '{}': {}", shell, e))?;
+        }
+    }
+
+    eprintln!("Worktree: {}", path.display());
+    Ok(())
+}
+
+/// Parse `--agent-env KEY=VALUE` pairs, rejecting malformed entries and invalid names.
+fn parse_agent_env(pairs: &[String]) -> Result<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+    for pair in pairs {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid --agent-env '{}': expected KEY=VALUE", pair))?;
+
+        let valid_key = key
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            anyhow::bail!("Invalid --agent-env variable name '{}'", key);
+        }
+
+        env.insert(key.to_string(), value.to_string());
+    }
+    Ok(env)
+}
+
+/// Branch-name matcher for `remove --pattern`.
+enum BranchMatcher {
+    Glob(globset::GlobMatcher),
+    Regex(regex::Regex),
+}
+
+impl BranchMatcher {
+    /// Compile `pattern` as a glob, or as a regex when `regex` is set.
+    fn new(pattern: &str, regex: bool) -> Result<Self> {
+        if regex {
+            regex::Regex::new(pattern)
+                .map(BranchMatcher::Regex)
+                .map_err(|e| anyhow::anyhow!("Invalid regex '{}': {}", pattern, e))
+        } else {
+            globset::Glob::new(pattern)
+                .map(|glob| BranchMatcher::Glob(glob.compile_matcher()))
+                .map_err(|e| anyhow::anyhow!("Invalid glob '{}': {}", pattern, e))
+        }
+    }
+
+    fn is_match(&self, branch: &str) -> bool {
+        match self {
+            BranchMatcher::Glob(glob) => glob.is_match(branch),
+            BranchMatcher::Regex(re) => re.is_match(branch),
+        }
+    }
+}
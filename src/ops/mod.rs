@@ -0,0 +1,431 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::PromptManager;
+use crate::WorktreeManager;
+use crate::agent::{ContainerAgent, PromptMode, SystemPrompt, check_min_version, get_agent};
+use crate::config::{
+    alias_dir, load_config, load_repo_config, workspaces_dir, worktrees_registry_path,
+};
+use crate::events::{self, Event};
+use crate::output;
+use crate::worktree::{COPY_IGNORED_MAX_SIZE, CreateOptions, WorktreeInfo, WorktreeStatus};
+
+/// Everything needed to create and prepare one worktree.
+#[derive(Debug, Default, Clone)]
+pub struct CreateRequest {
+    pub branch: String,
+    /// Agent recorded for the worktree and launched by [`launch`]
+    pub agent: String,
+    pub options: CreateOptions,
+    /// Move uncommitted changes from the project checkout into the worktree
+    pub move_changes: bool,
+    /// Copy uncommitted changes to tracked files into the worktree, keeping the originals
+    pub snapshot_changes: bool,
+    /// Directory whose contents are copied into the worktree
+    pub template: Option<PathBuf>,
+    /// Overwrite existing files when copying the template
+    pub overwrite_template: bool,
+    /// Copy git-ignored files from the project root
+    pub copy_ignored: bool,
+    /// Return the branch's existing worktree, unchanged, instead of failing
+    pub attach_if_exists: bool,
+    /// Push the new branch to this remote; a failed push only warns
+    pub push: Option<String>,
+    /// Fail instead of warning when the push fails
+    pub require_push: bool,
+    /// Block until this check passes before returning
+    pub wait_ready: Option<ReadyCheck>,
+}
+
+/// A readiness probe: `command` is run in the worktree until it exits 0.
+#[derive(Debug, Clone)]
+pub struct ReadyCheck {
+    pub command: String,
+    pub timeout: Duration,
+}
+
+/// How to start the agent (or a replacement command) in a worktree.
+#[derive(Debug, Default, Clone)]
+pub struct LaunchOptions {
+    /// Name of a prompt in the prompt library
+    pub system_prompt: Option<String>,
+    pub prompt_mode: PromptMode,
+    /// Absolute path; the agent runs inside the worktree
+    pub mcp_config: Option<PathBuf>,
+    pub agent_env: BTreeMap<String, String>,
+    pub agent_args: Vec<String>,
+    /// Shell command run instead of the agent
+    pub command: Option<String>,
+    /// Run the agent inside a container from this image instead of on the host
+    pub container: Option<String>,
+}
+
+/// Create the worktree described by `request` and prepare it (template, ignored files).
+/// With `attach_if_exists`, an existing worktree for the branch is returned as is.
+pub fn create(manager: &WorktreeManager, request: &CreateRequest) -> Result<WorktreeInfo> {
+    if request.attach_if_exists
+        && let Some(existing) = manager.find_by_branch(&request.branch)?
+        && existing.path.exists()
+    {
+        eprintln!(
+            "Reusing existing worktree for '{}' (agent: {})",
+            existing.branch, existing.agent
+        );
+        warn_on_mismatch(&existing, request);
+        return Ok(existing);
+    }
+
+    let mut worktree_info = if request.move_changes {
+        manager.create_worktree_with_changes(&request.branch, &request.agent, &request.options)?
+    } else {
+        manager.create_worktree(&request.branch, &request.agent, &request.options)?
+    };
+
+    if request.snapshot_changes {
+        match manager.snapshot_changes(&worktree_info.path) {
+            Ok(true) => eprintln!("Copied uncommitted changes into the worktree"),
+            Ok(false) => eprintln!("No uncommitted changes to copy"),
+            Err(e) => eprintln!("Warning: {:#}", e),
+        }
+    }
+
+    if let Some(template) = &request.template {
+        let copied =
+            manager.apply_template(template, &worktree_info.path, request.overwrite_template)?;
+        eprintln!(
+            "Copied {} files from template {}",
+            copied,
+            template.display()
+        );
+    }
+
+    if request.copy_ignored {
+        let (copied, too_large) = manager.copy_ignored_files(&worktree_info.path)?;
+        eprintln!("Copied {} ignored files", copied);
+        for path in too_large {
+            eprintln!(
+                "Skipped {} (larger than {})",
+                path.display(),
+                output::format_size(COPY_IGNORED_MAX_SIZE)
+            );
+        }
+    }
+
+    if let Some(check) = &request.wait_ready {
+        wait_until_ready(&worktree_info, check)?;
+    }
+
+    if let Some(remote) = &request.push {
+        match manager.push_branch(&mut worktree_info, remote) {
+            Ok(()) => {}
+            Err(e) if request.require_push => return Err(e),
+            Err(e) => eprintln!("Warning: {:#}", e),
+        }
+    }
+
+    Ok(worktree_info)
+}
+
+/// Run the worktree's agent, or `options.command` if given, in the foreground
+/// and wait for it to exit. Lifecycle events are emitted around the run.
+pub fn launch(worktree_info: &WorktreeInfo, options: &LaunchOptions) -> Result<()> {
+    let mut agent = get_agent(&worktree_info.agent)?;
+    if let Some(image) = &options.container {
+        agent = Box::new(ContainerAgent::new(agent, image)?);
+    }
+    let launched = match &options.command {
+        Some(command) => command.clone(),
+        None => agent.name().to_string(),
+    };
+    events::emit(Event::AgentStarted {
+        worktree_id: &worktree_info.id,
+        branch: &worktree_info.branch,
+        agent: &launched,
+    });
+
+    let result = if let Some(command) = &options.command {
+        run_custom_command(worktree_info, command, &options.agent_env)
+    } else {
+        let system_prompt = options.system_prompt.as_deref().map(|name| SystemPrompt {
+            name,
+            mode: options.prompt_mode,
+        });
+        agent.start(
+            worktree_info,
+            system_prompt,
+            options.mcp_config.as_deref(),
+            &options.agent_env,
+            &options.agent_args,
+        )
+    };
+
+    events::emit(Event::AgentExited {
+        worktree_id: &worktree_info.id,
+        branch: &worktree_info.branch,
+        agent: &launched,
+        success: result.is_ok(),
+    });
+    result
+}
+
+/// Create a worktree and launch its agent: [`create`] followed by [`launch`].
+pub fn run_create(
+    manager: &WorktreeManager,
+    request: &CreateRequest,
+    launch_options: &LaunchOptions,
+) -> Result<WorktreeInfo> {
+    let worktree_info = create(manager, request)?;
+    launch(&worktree_info, launch_options)?;
+    Ok(worktree_info)
+}
+
+/// Bail if the installed agent is older than `agent_min_version` from `.maokai.toml`
+/// (checked first) or the user config.
+pub fn check_agent_version(agent: &str, project_root: &Path) -> Result<()> {
+    let min_version = match load_repo_config(project_root)?
+        .agent_min_version
+        .remove(agent)
+    {
+        Some(version) => Some(version),
+        None => load_config()?.agent_min_version.remove(agent),
+    };
+
+    match min_version {
+        Some(min_version) => check_min_version(get_agent(agent)?.as_ref(), &min_version),
+        None => Ok(()),
+    }
+}
+
+/// Warn when a reused worktree was not set up the way `request` asks, since reusing it
+/// means the requested base and agent are not applied.
+fn warn_on_mismatch(existing: &WorktreeInfo, request: &CreateRequest) {
+    if existing.agent != request.agent {
+        eprintln!(
+            "Warning: requested agent '{}', but the existing worktree uses '{}'",
+            request.agent, existing.agent
+        );
+    }
+
+    if let Some(base) = &request.options.base_branch {
+        // With --remote the recorded base is the remote-tracking branch
+        let matches = |recorded: &str| {
+            recorded == base
+                || request
+                    .options
+                    .remote
+                    .as_ref()
+                    .is_some_and(|remote| recorded == format!("{}/{}", remote, base))
+        };
+        match existing.base_branch.as_deref() {
+            Some(recorded) if matches(recorded) => {}
+            Some(recorded) => eprintln!(
+                "Warning: requested base branch '{}', but the existing worktree was created from '{}'",
+                base, recorded
+            ),
+            None => eprintln!(
+                "Warning: requested base branch '{}', but the existing worktree's base is unknown",
+                base
+            ),
+        }
+    }
+}
+
+/// Remove every worktree in the registry, then delete the registry, workspaces and
+/// aliases (and the prompt library with `prompts`), reporting each step. Worktrees with
+/// uncommitted changes stop the reset before anything is touched, unless `force`. If a
+/// worktree can't be removed, the remaining state is kept so the reset can be retried.
+pub fn reset(
+    manager: &WorktreeManager,
+    base_path: &Path,
+    force: bool,
+    prompts: bool,
+) -> Result<()> {
+    let worktrees = manager.list_all_worktrees()?;
+
+    if !force {
+        let mut dirty = Vec::new();
+        for wt in &worktrees {
+            if let Some(state) = manager.worktree_git_state(&wt.path)?
+                && state.is_dirty()
+            {
+                dirty.push(wt);
+            }
+        }
+        if !dirty.is_empty() {
+            for wt in &dirty {
+                eprintln!("  {} ({})", wt.branch, output::display_path(&wt.path));
+            }
+            anyhow::bail!(
+                "{} worktrees have uncommitted changes; commit them or pass --force",
+                dirty.len()
+            );
+        }
+    }
+
+    let mut failed = 0;
+    for wt in &worktrees {
+        if !wt.path.exists() {
+            eprintln!(
+                "Skipped {} (directory already gone)",
+                output::display_path(&wt.path)
+            );
+            continue;
+        }
+        // Worktrees belong to many projects, so run git from each one's own root
+        let owner = WorktreeManager::new(wt.project_root.clone(), base_path.to_path_buf());
+        match owner.remove_worktree_at_path(&wt.path, &wt.branch, force) {
+            Ok(()) => eprintln!(
+                "Removed worktree {} ({})",
+                wt.branch,
+                output::display_path(&wt.path)
+            ),
+            Err(e) => {
+                eprintln!(
+                    "Failed to remove {}: {:#}",
+                    output::display_path(&wt.path),
+                    e
+                );
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!(
+            "{} worktrees could not be removed; the registry, workspaces and aliases were kept",
+            failed
+        );
+    }
+
+    let registry = worktrees_registry_path();
+    if registry.exists() {
+        std::fs::remove_file(&registry)
+            .with_context(|| format!("Failed to delete {}", registry.display()))?;
+        eprintln!("Deleted registry {}", registry.display());
+    }
+
+    let mut dirs = vec![workspaces_dir(), alias_dir()];
+    if prompts {
+        dirs.push(PromptManager::new()?.prompts_dir().clone());
+    }
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("Failed to delete {}", dir.display()))?;
+        eprintln!("Deleted {}", dir.display());
+    }
+
+    Ok(())
+}
+
+/// Warn on stderr when more of `worktrees` are active than `warn_worktree_count` allows,
+/// since many stale worktrees slow git down and fill the disk. `scope` names what was
+/// counted, e.g. "in this project".
+pub fn warn_on_worktree_count(worktrees: &[WorktreeInfo], scope: &str) -> Result<()> {
+    let limit = load_config()?.warn_worktree_count();
+    let active = worktrees
+        .iter()
+        .filter(|wt| matches!(wt.status, WorktreeStatus::Active))
+        .count();
+    if limit > 0 && active > limit {
+        eprintln!(
+            "Warning: {} active worktrees {} (more than warn_worktree_count = {}); consider removing finished ones with `maokai remove`",
+            active, scope, limit
+        );
+    }
+    Ok(())
+}
+
+/// Poll `check.command` in the worktree every second until it succeeds or the timeout
+/// passes.
+fn wait_until_ready(worktree_info: &WorktreeInfo, check: &ReadyCheck) -> Result<()> {
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    eprintln!("Waiting for worktree to be ready ({})...", check.command);
+    let started = Instant::now();
+    loop {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&check.command)
+            .current_dir(&worktree_info.path)
+            .envs(worktree_info.env_vars())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .with_context(|| format!("Failed to run ready check '{}'", check.command))?;
+        if status.success() {
+            eprintln!("Worktree ready after {}s", started.elapsed().as_secs());
+            return Ok(());
+        }
+        if started.elapsed() >= check.timeout {
+            anyhow::bail!(
+                "Worktree not ready after {}s: '{}' still fails (the worktree was kept)",
+                check.timeout.as_secs(),
+                check.command
+            );
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Run `command` (a program and its arguments, no shell) inside the worktree with the
+/// `MAOKAI_*` variables set, inheriting stdio. Returns the exit code to pass on; a
+/// child killed by a signal maps to 128 + the signal number, as in a shell.
+pub fn exec(worktree_info: &WorktreeInfo, command: &[String]) -> Result<i32> {
+    let Some((program, args)) = command.split_first() else {
+        anyhow::bail!(
+            "No command given; pass it after --, e.g. `maokai exec <branch> -- npm test`"
+        );
+    };
+
+    let status = Command::new(program)
+        .args(args)
+        .current_dir(&worktree_info.path)
+        .envs(worktree_info.env_vars())
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .with_context(|| format!("Failed to run '{}'", program))?;
+
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return Ok(128 + signal);
+    }
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Run `command` with `sh -c` inside the worktree.
+fn run_custom_command(
+    worktree_info: &WorktreeInfo,
+    command: &str,
+    agent_env: &BTreeMap<String, String>,
+) -> Result<()> {
+    if command.trim().is_empty() {
+        anyhow::bail!("Empty custom command");
+    }
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd.current_dir(&worktree_info.path);
+
+    // Set environment variables with worktree info
+    cmd.envs(worktree_info.env_vars());
+    cmd.envs(agent_env);
+
+    cmd.stdin(Stdio::inherit());
+    cmd.stdout(Stdio::inherit());
+    cmd.stderr(Stdio::inherit());
+
+    let status = cmd
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to execute custom command '{}': {}", command, e))?;
+
+    if !status.success() {
+        anyhow::bail!("Custom command failed with exit code: {:?}", status.code());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,386 @@
+use anyhow::Result;
+use owo_colors::{OwoColorize, Stream};
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::agent::AgentDescriptor;
+use crate::selftest::StepResult;
+use crate::worktree::{GitState, GitSummary, WorktreeInfo, WorktreeStatus};
+
+/// Print `value` to stdout as pretty-printed JSON, for `--json` modes.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Force plain output regardless of TTY detection. Color is otherwise enabled only when
+/// stdout is a terminal and `NO_COLOR` is unset.
+pub fn disable_color() {
+    owo_colors::set_override(false);
+}
+
+/// Base that printed worktree paths are made relative to (`--relative`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RelativeTo {
+    /// `~/...` for paths under the home directory
+    Home,
+    /// Relative to the current directory, e.g. `../maokai/worktrees/...`
+    Cwd,
+}
+
+static RELATIVE_TO: OnceLock<RelativeTo> = OnceLock::new();
+
+/// Print worktree paths relative to `base` for the rest of the run.
+pub fn set_relative_paths(base: RelativeTo) {
+    let _ = RELATIVE_TO.set(base);
+}
+
+/// A worktree path as printed: absolute unless `--relative` was given. Paths that
+/// can't be expressed relative to the base are printed as is.
+pub fn display_path(path: &Path) -> String {
+    let relative = match RELATIVE_TO.get() {
+        None => None,
+        Some(RelativeTo::Home) => dirs::home_dir().and_then(|home| {
+            path.strip_prefix(&home)
+                .ok()
+                .map(|rest| Path::new("~").join(rest))
+        }),
+        Some(RelativeTo::Cwd) => std::env::current_dir()
+            .ok()
+            .and_then(|cwd| relative_path(path, &cwd)),
+    };
+    relative.as_deref().unwrap_or(path).display().to_string()
+}
+
+/// `path` relative to the directory `base`, both absolute. None if they share no root.
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    if path_components.peek() != base_components.peek() {
+        return None;
+    }
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek())
+        && a == b
+    {
+        path_components.next();
+        base_components.next();
+    }
+
+    let relative: PathBuf = base_components
+        .map(|_| Component::ParentDir)
+        .chain(path_components)
+        .collect();
+    Some(if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    })
+}
+
+pub fn format_status(status: &WorktreeStatus) -> String {
+    let label = format!("{:?}", status);
+    match status {
+        WorktreeStatus::Active => label
+            .if_supports_color(Stream::Stdout, |t| t.green())
+            .to_string(),
+        WorktreeStatus::Paused => label
+            .if_supports_color(Stream::Stdout, |t| t.yellow())
+            .to_string(),
+        WorktreeStatus::Completed => label
+            .if_supports_color(Stream::Stdout, |t| t.dimmed())
+            .to_string(),
+    }
+}
+
+/// Uncolored `project - branch (agent)`, as shown in the `switch` picker.
+pub fn worktree_label(wt: &WorktreeInfo) -> String {
+    format!("{} - {} ({})", wt.project_name, wt.branch, wt.agent)
+}
+
+pub fn print_worktree_line(wt: &WorktreeInfo) {
+    println!(
+        "{} - {} ({})",
+        wt.project_name
+            .if_supports_color(Stream::Stdout, |t| t.cyan()),
+        wt.branch.if_supports_color(Stream::Stdout, |t| t.bold()),
+        wt.agent.if_supports_color(Stream::Stdout, |t| t.dimmed())
+    );
+}
+
+/// Print one `status` entry, with a git line when `git_state` is given.
+pub fn print_worktree_status(wt: &WorktreeInfo, git_state: Option<&GitSummary>) {
+    println!(
+        "  Branch: {}",
+        wt.branch.if_supports_color(Stream::Stdout, |t| t.bold())
+    );
+    println!("    Path: {}", display_path(&wt.path));
+    println!("    Agent: {}", wt.agent);
+    println!("    Status: {}", format_status(&wt.status));
+    println!(
+        "    Created: {}",
+        wt.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let Some(state) = git_state {
+        println!("    Git: {}", format_git_summary(state));
+        println!(
+            "    Upstream: {}",
+            format_upstream(state.upstream.as_deref(), state.ahead, state.behind)
+        );
+        if let Some(reason) = &state.locked {
+            println!("    Locked: {}", format_lock(reason));
+        }
+    } else {
+        println!(
+            "    Git: {}",
+            "directory missing".if_supports_color(Stream::Stdout, |t| t.red())
+        );
+    }
+    println!();
+}
+
+/// e.g. `feature/x, dirty`
+pub fn format_git_summary(state: &GitSummary) -> String {
+    let mut parts = vec![
+        state
+            .current_branch
+            .clone()
+            .unwrap_or_else(|| "(detached HEAD)".to_string()),
+    ];
+    if state.dirty {
+        let files = if state.changed_files == 1 {
+            "file"
+        } else {
+            "files"
+        };
+        parts.push(
+            format!("dirty ({} {})", state.changed_files, files)
+                .if_supports_color(Stream::Stdout, |t| t.yellow())
+                .to_string(),
+        );
+    } else {
+        parts.push(
+            "clean"
+                .if_supports_color(Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+    }
+    parts.join(", ")
+}
+
+/// e.g. `PASS create worktree`, with the error on the next line for a failure.
+pub fn print_selftest_step(step: &StepResult) {
+    match &step.error {
+        None => {
+            let note = step
+                .note
+                .as_ref()
+                .map(|note| format!(" ({})", note))
+                .unwrap_or_default();
+            println!(
+                "{} {}{}",
+                "PASS".if_supports_color(Stream::Stdout, |t| t.green()),
+                step.name,
+                note
+            );
+        }
+        Some(error) => {
+            println!(
+                "{} {}",
+                "FAIL".if_supports_color(Stream::Stdout, |t| t.red()),
+                step.name
+            );
+            println!("     {}", error);
+        }
+    }
+}
+
+/// Print a unified diff, coloring added and removed lines.
+pub fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.if_supports_color(Stream::Stdout, |t| t.bold()));
+        } else if line.starts_with('+') {
+            println!("{}", line.if_supports_color(Stream::Stdout, |t| t.green()));
+        } else if line.starts_with('-') {
+            println!("{}", line.if_supports_color(Stream::Stdout, |t| t.red()));
+        } else if line.starts_with("@@") {
+            println!("{}", line.if_supports_color(Stream::Stdout, |t| t.cyan()));
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Human-readable byte count (e.g. `12.3 MB`).
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// `yes`, or the lock reason when one was given.
+fn format_lock(reason: &str) -> String {
+    if reason.is_empty() {
+        "yes".to_string()
+    } else {
+        reason.to_string()
+    }
+}
+
+/// Upstream and ahead/behind summary, e.g. `origin/foo [ahead 2, behind 1]`.
+pub fn format_upstream(upstream: Option<&str>, ahead: u32, behind: u32) -> String {
+    let Some(upstream) = upstream else {
+        return "(no upstream)"
+            .if_supports_color(Stream::Stdout, |t| t.yellow())
+            .to_string();
+    };
+
+    let mut counts = Vec::new();
+    if ahead > 0 {
+        counts.push(format!("ahead {}", ahead));
+    }
+    if behind > 0 {
+        counts.push(format!("behind {}", behind));
+    }
+
+    if counts.is_empty() {
+        upstream.to_string()
+    } else {
+        format!("{} [{}]", upstream, counts.join(", "))
+    }
+}
+
+pub fn print_worktree_info(wt: &WorktreeInfo, git_state: Option<&GitState>, disk_usage: u64) {
+    println!(
+        "Branch: {}",
+        wt.branch.if_supports_color(Stream::Stdout, |t| t.bold())
+    );
+    println!("  ID: {}", wt.id);
+    println!(
+        "  Project: {} ({})",
+        wt.project_name,
+        wt.project_root.display()
+    );
+    println!("  Path: {}", display_path(&wt.path));
+    println!("  Agent: {}", wt.agent);
+    println!("  Status: {}", format_status(&wt.status));
+    println!(
+        "  Base branch: {}",
+        wt.base_branch.as_deref().unwrap_or("(unknown)")
+    );
+    println!(
+        "  Created: {}",
+        wt.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let Some(remote) = &wt.pushed_to {
+        println!("  Pushed to: {}", remote);
+    }
+    if let Some(issue) = &wt.issue {
+        println!("  Issue: {}", issue);
+    }
+
+    let Some(state) = git_state else {
+        println!("  Git: (worktree directory missing)");
+        return;
+    };
+
+    println!(
+        "  Checked out: {}",
+        state.branch.as_deref().unwrap_or("(detached HEAD)")
+    );
+    println!(
+        "  Upstream: {}",
+        format_upstream(state.upstream.as_deref(), state.ahead, state.behind)
+    );
+    if let Some(reason) = &state.locked {
+        println!("  Locked: {}", format_lock(reason));
+    }
+    if state.is_dirty() {
+        println!(
+            "  Changes: {} files",
+            state
+                .dirty_files
+                .len()
+                .if_supports_color(Stream::Stdout, |t| t.yellow())
+        );
+        for file in &state.dirty_files {
+            println!("    {}", file);
+        }
+    } else {
+        println!(
+            "  Changes: {}",
+            "clean".if_supports_color(Stream::Stdout, |t| t.green())
+        );
+    }
+    println!("  Disk usage: {}", format_size(disk_usage));
+}
+
+/// Shell syntax for `maokai env`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ShellFormat {
+    /// `export KEY='value'` (bash, zsh, sh)
+    #[default]
+    Sh,
+    /// `set -gx KEY 'value'`
+    Fish,
+}
+
+/// Quote `value` as a single POSIX shell word.
+pub fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// One line that sets `key` to `value` in the given shell, with the value quoted.
+pub fn format_env_line(format: ShellFormat, key: &str, value: &str) -> String {
+    match format {
+        ShellFormat::Sh => format!("export {}={}", key, sh_quote(value)),
+        ShellFormat::Fish => format!(
+            "set -gx {} '{}'",
+            key,
+            value.replace('\\', "\\\\").replace('\'', "\\'")
+        ),
+    }
+}
+
+/// One line per agent, e.g. `claude (Claude) installed 1.0.43 [system prompt, model, mcp]`.
+pub fn print_agent(agent: &AgentDescriptor) {
+    let availability = match (&agent.version, agent.available) {
+        (Some(version), _) => format!("installed {}", version),
+        (None, true) => "installed".to_string(),
+        (None, false) => "not installed".to_string(),
+    };
+    let capabilities: Vec<&str> = [
+        (agent.capabilities.supports_system_prompt, "system prompt"),
+        (agent.capabilities.supports_model, "model"),
+        (agent.capabilities.supports_mcp, "mcp"),
+    ]
+    .into_iter()
+    .filter_map(|(supported, name)| supported.then_some(name))
+    .collect();
+
+    println!(
+        "{} ({}) {} [{}]",
+        agent.name.if_supports_color(Stream::Stdout, |t| t.bold()),
+        agent.label,
+        if agent.available {
+            availability
+                .if_supports_color(Stream::Stdout, |t| t.green())
+                .to_string()
+        } else {
+            availability
+                .if_supports_color(Stream::Stdout, |t| t.dimmed())
+                .to_string()
+        },
+        capabilities.join(", ")
+    );
+}
@@ -39,6 +39,19 @@ impl PromptManager {
             .with_context(|| format!("Failed to read prompt file: {}", prompt_path.display()))
     }
 
+    /// Unified diff from prompt `a` to prompt `b`; empty if they are identical.
+    pub fn diff_prompts(&self, a: &str, b: &str) -> Result<String> {
+        let old = self.load_prompt(a)?;
+        let new = self.load_prompt(b)?;
+        let old_name = self.get_prompt_path(a).display().to_string();
+        let new_name = self.get_prompt_path(b).display().to_string();
+
+        Ok(similar::TextDiff::from_lines(&old, &new)
+            .unified_diff()
+            .header(&old_name, &new_name)
+            .to_string())
+    }
+
     pub fn list_prompts(&self) -> Result<Vec<String>> {
         let mut prompts = Vec::new();
 
@@ -53,12 +66,12 @@ impl PromptManager {
             let entry = entry.context("Failed to read directory entry")?;
             let path = entry.path();
 
-            if path.is_file() && path.extension().is_some_and(|ext| ext == "md") {
-                if let Some(stem) = path.file_stem() {
-                    if let Some(name) = stem.to_str() {
-                        prompts.push(name.to_string());
-                    }
-                }
+            if path.is_file()
+                && path.extension().is_some_and(|ext| ext == "md")
+                && let Some(stem) = path.file_stem()
+                && let Some(name) = stem.to_str()
+            {
+                prompts.push(name.to_string());
             }
         }
 
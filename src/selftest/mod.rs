@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use crate::config::git_program;
+
+const BRANCH: &str = "maokai-selftest";
+
+/// A step returns a short note to show on success, e.g. the git version.
+type Step = fn(&SelftestEnv) -> Result<Option<String>>;
+
+/// Outcome of one self-test step.
+pub struct StepResult {
+    pub name: &'static str,
+    pub note: Option<String>,
+    /// None on success, otherwise what went wrong
+    pub error: Option<String>,
+}
+
+/// Exercise create, list and remove end to end: a throwaway repository is created in a
+/// temporary directory and this executable is run against it with `HOME` and
+/// `MAOKAI_WORKTREE_PATH` pointing into that directory, so the user's registry and
+/// worktrees are never touched. Steps after a failure are skipped. Everything is deleted
+/// afterwards.
+pub fn run() -> Result<Vec<StepResult>> {
+    let temp = tempfile::tempdir().context("Failed to create temporary directory")?;
+    let env = SelftestEnv::new(temp.path())?;
+
+    let steps: [(&'static str, Step); 5] = [
+        ("git is available", SelftestEnv::check_git),
+        ("init repository", SelftestEnv::init_repo),
+        ("create worktree", SelftestEnv::create),
+        ("list worktrees", SelftestEnv::list),
+        ("remove worktree", SelftestEnv::remove),
+    ];
+
+    let mut results = Vec::new();
+    for (name, step) in steps {
+        let (note, error) = match step(&env) {
+            Ok(note) => (note, None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        };
+        let failed = error.is_some();
+        results.push(StepResult { name, note, error });
+        if failed {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+struct SelftestEnv {
+    exe: PathBuf,
+    home: PathBuf,
+    repo: PathBuf,
+    worktrees: PathBuf,
+}
+
+impl SelftestEnv {
+    fn new(root: &Path) -> Result<Self> {
+        let env = Self {
+            exe: std::env::current_exe().context("Failed to locate the maokai executable")?,
+            home: root.join("home"),
+            repo: root.join("repo"),
+            worktrees: root.join("worktrees"),
+        };
+        std::fs::create_dir_all(&env.home)?;
+        std::fs::create_dir_all(&env.repo)?;
+        Ok(env)
+    }
+
+    fn git(&self, args: &[&str]) -> Result<Output> {
+        let output = Command::new(git_program())
+            .args(args)
+            .current_dir(&self.repo)
+            .env("HOME", &self.home)
+            .env("GIT_AUTHOR_NAME", "maokai selftest")
+            .env("GIT_AUTHOR_EMAIL", "selftest@maokai.invalid")
+            .env("GIT_COMMITTER_NAME", "maokai selftest")
+            .env("GIT_COMMITTER_EMAIL", "selftest@maokai.invalid")
+            .output()
+            .with_context(|| format!("Failed to run {}", git_program().display()))?;
+        check(&output, "git")?;
+        Ok(output)
+    }
+
+    /// Run this maokai executable in the throwaway repository.
+    fn maokai(&self, args: &[&str]) -> Result<Output> {
+        let output = Command::new(&self.exe)
+            .arg("--plain")
+            .args(args)
+            .current_dir(&self.repo)
+            .env("HOME", &self.home)
+            .env("MAOKAI_WORKTREE_PATH", &self.worktrees)
+            // The user config isn't visible under the temporary HOME
+            .env("MAOKAI_GIT", git_program())
+            .env_remove("MAOKAI_EVENTS")
+            .output()
+            .context("Failed to run maokai")?;
+        check(&output, "maokai")?;
+        Ok(output)
+    }
+
+    fn check_git(&self) -> Result<Option<String>> {
+        let output = self.git(&["--version"])?;
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    }
+
+    fn init_repo(&self) -> Result<Option<String>> {
+        self.git(&["init", "--quiet"])?;
+        self.git(&["commit", "--quiet", "--allow-empty", "-m", "selftest"])?;
+        Ok(None)
+    }
+
+    fn create(&self) -> Result<Option<String>> {
+        let output = self.maokai(&["create", BRANCH, "--no-agent"])?;
+        let path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        if !path.is_dir() {
+            anyhow::bail!("worktree directory {} was not created", path.display());
+        }
+        if !self.home.join(".maokai/worktrees.json").exists() {
+            anyhow::bail!("the registry was not written");
+        }
+        Ok(None)
+    }
+
+    fn list(&self) -> Result<Option<String>> {
+        let output = self.maokai(&["ls", "--branches-only"])?;
+        if !String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|line| line == BRANCH)
+        {
+            anyhow::bail!("'{}' is missing from `ls`", BRANCH);
+        }
+        Ok(None)
+    }
+
+    fn remove(&self) -> Result<Option<String>> {
+        self.maokai(&["remove", BRANCH, "--yes"])?;
+        let output = self.git(&["branch", "--list", BRANCH])?;
+        if !output.stdout.is_empty() {
+            anyhow::bail!("branch '{}' was not deleted", BRANCH);
+        }
+        let leftover = std::fs::read_dir(&self.worktrees)
+            .map(|entries| entries.count())
+            .unwrap_or(0);
+        if leftover > 0 {
+            anyhow::bail!("worktree directory was not removed");
+        }
+        Ok(None)
+    }
+}
+
+fn check(output: &Output, program: &str) -> Result<()> {
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use dialoguer::{Confirm, Select};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Force scriptable behavior (`--plain`): no prompts, pauses or color, even on a TTY.
+pub fn set_plain() {
+    PLAIN.store(true, Ordering::Relaxed);
+}
+
+/// Whether plain mode is on, via `--plain` or a truthy `CI` environment variable.
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+        || std::env::var("CI").is_ok_and(|v| !v.is_empty() && v != "0" && v != "false")
+}
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Answer yes to every confirmation prompt (`--yes`).
+pub fn set_assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
+
+/// Whether both stdin and stderr are attached to a terminal, i.e. a user can answer
+/// prompts. Always false in plain mode.
+pub fn is_interactive() -> bool {
+    !is_plain() && std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Ask a yes/no question on stderr, defaulting to "no". Returns true without prompting
+/// under `--yes` or when not running interactively, so scripts never block.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    if assume_yes() || !is_interactive() {
+        return Ok(true);
+    }
+
+    Ok(Confirm::new()
+        .with_prompt(prompt)
+        .default(false)
+        .interact()?)
+}
+
+/// Let the user pick one of `items` with the arrow keys, drawn on stderr so stdout stays
+/// free for the result. Returns None if they cancel with Esc or q. Only call this when
+/// [`is_interactive`].
+pub fn select(prompt: &str, items: &[String]) -> Result<Option<usize>> {
+    Ok(Select::new()
+        .with_prompt(prompt)
+        .items(items)
+        .default(0)
+        .interact_opt()?)
+}
@@ -1,24 +1,57 @@
 use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::config::alias_dir;
 
 use super::editor::open_in_editor;
+use super::{collect_projects, validate_projects};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AliasConfig {
     pub name: String,
+    #[serde(default)]
     pub projects: Vec<PathBuf>,
+    /// Pattern whose matching git repositories are added to `projects` on load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+}
+
+/// Parse a project-list file, choosing TOML or YAML by the file extension (YAML unless
+/// it ends in `.toml`).
+pub fn parse_project_file<T: DeserializeOwned>(path: &Path, content: &str) -> Result<T> {
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        Ok(toml::from_str(content)?)
+    } else {
+        Ok(serde_yaml_ng::from_str(content)?)
+    }
 }
 
 pub struct AliasManager;
 
+impl Default for AliasManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AliasManager {
     pub fn new() -> Self {
         Self
     }
 
+    /// The alias file: `<name>.yml`, or `<name>.toml` if only that exists.
+    fn alias_path(&self, alias_name: &str) -> PathBuf {
+        let yaml = alias_dir().join(format!("{}.yml", alias_name));
+        let toml = alias_dir().join(format!("{}.toml", alias_name));
+        if !yaml.exists() && toml.exists() {
+            toml
+        } else {
+            yaml
+        }
+    }
+
     pub fn create(&self, alias_name: &str) -> Result<()> {
         let alias_path = alias_dir().join(format!("{}.yml", alias_name));
         std::fs::create_dir_all(alias_dir())?;
@@ -31,6 +64,9 @@ name: {}
 projects:
 #  - /path/to/your/first/project
 #  - /path/to/your/second/project
+
+# Optionally add every git repository matching a glob:
+# glob: ~/work/*/
 "#,
             alias_name
         );
@@ -51,18 +87,20 @@ projects:
     }
 
     pub fn load(&self, alias_name: &str) -> Result<AliasConfig> {
-        let alias_path = alias_dir().join(format!("{}.yml", alias_name));
+        let alias_path = self.alias_path(alias_name);
         let content = std::fs::read_to_string(&alias_path)
             .with_context(|| format!("Failed to read alias '{}'", alias_name))?;
-        let config: AliasConfig = serde_yaml::from_str(&content)
+        let mut config: AliasConfig = parse_project_file(&alias_path, &content)
             .with_context(|| format!("Failed to parse alias '{}'", alias_name))?;
 
-        self.validate_projects(&config.projects)?;
+        let projects = validate_projects(&config.projects)?;
+        // Expanded on every load, so repos added under the glob are picked up
+        config.projects = collect_projects(&projects, config.glob.as_deref())?;
         Ok(config)
     }
 
     pub fn remove(&self, alias_name: &str) -> Result<()> {
-        let alias_path = alias_dir().join(format!("{}.yml", alias_name));
+        let alias_path = self.alias_path(alias_name);
         if !alias_path.exists() {
             anyhow::bail!("Alias '{}' not found", alias_name);
         }
@@ -81,41 +119,65 @@ projects:
         for entry in std::fs::read_dir(dir)? {
             let entry = entry?;
             let path = entry.path();
-            if path.extension().map(|e| e == "yml").unwrap_or(false) {
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    aliases.push(stem.to_string());
-                }
+            if path.extension().is_some_and(|e| e == "yml" || e == "toml")
+                && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+            {
+                aliases.push(stem.to_string());
             }
         }
         aliases.sort();
+        aliases.dedup();
         Ok(aliases)
     }
 
     fn validate_alias_file(&self, path: &PathBuf) -> Result<()> {
         let content = std::fs::read_to_string(path).context("Failed to read alias file")?;
         let config: AliasConfig =
-            serde_yaml::from_str(&content).context("Failed to parse alias file")?;
+            parse_project_file(path, &content).context("Failed to parse alias file")?;
+
+        let projects = validate_projects(&config.projects)?;
 
-        if config.projects.is_empty() {
+        if collect_projects(&projects, config.glob.as_deref())?.is_empty() {
             anyhow::bail!("Alias must have at least one project");
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn existing_alias_yaml_round_trips() {
+        let path = Path::new("work.yml");
+        let content = "# Maokai Workspace Alias\nname: work\nprojects:\n  - /src/api\n  - /src/web\nglob: ~/work/*/\n";
 
-        self.validate_projects(&config.projects)
+        let config: AliasConfig = parse_project_file(path, content).unwrap();
+        assert_eq!(config.name, "work");
+        assert_eq!(
+            config.projects,
+            [PathBuf::from("/src/api"), PathBuf::from("/src/web")]
+        );
+        assert_eq!(config.glob.as_deref(), Some("~/work/*/"));
+
+        let written = serde_yaml_ng::to_string(&config).unwrap();
+        let reparsed: AliasConfig = parse_project_file(path, &written).unwrap();
+        assert_eq!(reparsed.name, config.name);
+        assert_eq!(reparsed.projects, config.projects);
+        assert_eq!(reparsed.glob, config.glob);
     }
 
-    fn validate_projects(&self, projects: &[PathBuf]) -> Result<()> {
-        for project in projects {
-            if !project.exists() {
-                anyhow::bail!("Project path does not exist: {}", project.display());
-            }
-            let git_path = project.join(".git");
-            if !git_path.exists() {
-                anyhow::bail!(
-                    "Project path is not a git repository: {}",
-                    project.display()
-                );
-            }
-        }
-        Ok(())
+    #[test]
+    fn alias_yaml_without_glob_or_projects_parses() {
+        let config: AliasConfig =
+            parse_project_file(Path::new("only-glob.yml"), "name: g\nglob: ~/work/*/\n").unwrap();
+        assert!(config.projects.is_empty());
+
+        let config: AliasConfig =
+            parse_project_file(Path::new("old.yml"), "name: old\nprojects:\n  - /src/api\n")
+                .unwrap();
+        assert_eq!(config.glob, None);
+        assert!(!serde_yaml_ng::to_string(&config).unwrap().contains("glob"));
     }
 }
@@ -1,8 +1,11 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::io::{self, BufRead, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
 
+use crate::agent::is_installed;
+use crate::ui;
+
 pub fn get_editor() -> String {
     std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string())
 }
@@ -15,7 +18,48 @@ pub fn is_vim_like(editor: &str) -> bool {
     matches!(basename, "vim" | "nvim" | "vi")
 }
 
+/// Editors that open their own window and return (or can run) without the terminal.
+pub fn is_gui_editor(editor: &str) -> bool {
+    let basename = Path::new(editor)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(editor);
+    matches!(
+        basename,
+        "code"
+            | "code-insiders"
+            | "codium"
+            | "cursor"
+            | "windsurf"
+            | "zed"
+            | "subl"
+            | "atom"
+            | "mate"
+            | "idea"
+            | "gvim"
+            | "mvim"
+            | "gedit"
+    )
+}
+
+/// Editor for a directory: `$EDITOR`, or VS Code when it is unset and installed, since
+/// the `vi` fallback is a poor way to browse a project.
+fn get_directory_editor() -> String {
+    if std::env::var_os("EDITOR").is_none() && is_installed("code") {
+        return "code".to_string();
+    }
+    get_editor()
+}
+
+/// Open `path` in the user's editor. Files are edited in the foreground, pausing
+/// afterwards for editors that return before the file is saved. A directory is opened
+/// as a project: GUI editors are started in the background so the command returns
+/// right away, terminal editors run until they exit.
 pub fn open_in_editor(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        return open_directory(path);
+    }
+
     let editor = get_editor();
     let vim_like = is_vim_like(&editor);
 
@@ -30,7 +74,7 @@ pub fn open_in_editor(path: &Path) -> Result<()> {
         anyhow::bail!("Editor exited with non-zero status");
     }
 
-    if !vim_like {
+    if !vim_like && ui::is_interactive() {
         eprint!("Press Enter to continue...");
         io::stderr().flush()?;
         let stdin = io::stdin();
@@ -39,3 +83,26 @@ pub fn open_in_editor(path: &Path) -> Result<()> {
 
     Ok(())
 }
+
+fn open_directory(path: &Path) -> Result<()> {
+    let editor = get_directory_editor();
+    let mut cmd = Command::new(&editor);
+    cmd.arg(path);
+
+    if is_gui_editor(&editor) {
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start editor '{}'", editor))?;
+        return Ok(());
+    }
+
+    let status = cmd
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor exited with non-zero status");
+    }
+    Ok(())
+}
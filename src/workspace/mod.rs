@@ -4,12 +4,17 @@ pub mod editor;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{debug, debug_span};
 
-use crate::config::workspaces_dir;
 use crate::WorktreeManager;
+use crate::config::{expand_path, workspaces_dir};
 
-use self::alias::AliasManager;
+use self::alias::{AliasManager, parse_project_file};
 use self::editor::open_in_editor;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,6 +22,10 @@ pub struct WorkspaceInfo {
     pub name: String,
     pub safe_name: String,
     pub projects: Vec<PathBuf>,
+    /// Worktree directory of each entry in `projects`, in the same order. Empty in
+    /// workspace files written before the paths were recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub worktrees: Vec<PathBuf>,
     pub alias: Option<String>,
     pub created_at: DateTime<Utc>,
 }
@@ -30,14 +39,87 @@ pub fn sanitize_name(name: &str) -> String {
         .collect()
 }
 
+/// Expand a `glob` pattern from a workspace or alias file (e.g. `~/work/*/`) to the git
+/// repositories it matches, sorted. Matches that are not git repositories are skipped.
+pub fn expand_project_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    let pattern = expand_path(Path::new(pattern))
+        .to_string_lossy()
+        .into_owned();
+
+    let mut projects: Vec<PathBuf> = glob::glob(&pattern)
+        .with_context(|| format!("Invalid project glob: {}", pattern))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.join(".git").exists())
+        .collect();
+    projects.sort();
+    Ok(projects)
+}
+
+/// Expand `~` and `$VAR` in explicitly listed project paths and check that each is a
+/// git repository. Returns the expanded paths.
+pub fn validate_projects(projects: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    projects
+        .iter()
+        .map(|project| {
+            let project = expand_path(project);
+            if !project.exists() {
+                anyhow::bail!("Project path does not exist: {}", project.display());
+            }
+            if !project.join(".git").exists() {
+                anyhow::bail!(
+                    "Project path is not a git repository: {}",
+                    project.display()
+                );
+            }
+            Ok(project)
+        })
+        .collect()
+}
+
+/// Explicit `projects` followed by any `glob` matches not already listed.
+pub fn collect_projects(projects: &[PathBuf], glob: Option<&str>) -> Result<Vec<PathBuf>> {
+    let mut all = projects.to_vec();
+    if let Some(pattern) = glob {
+        for project in expand_project_glob(pattern)? {
+            if !all.contains(&project) {
+                all.push(project);
+            }
+        }
+    }
+    Ok(all)
+}
+
+/// Default number of concurrent worktree creations for a workspace.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
 pub struct WorkspaceManager;
 
+impl Default for WorkspaceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WorkspaceManager {
     pub fn new() -> Self {
         Self
     }
 
-    pub fn create(&self, name: &str, alias_name: Option<&str>) -> Result<()> {
+    /// Create a worktree named `name` in every project of the workspace. Projects are
+    /// processed in parallel, with at most `jobs` git operations running at once.
+    /// Projects that fail are skipped, unless `atomic` is set, in which case every
+    /// worktree created so far is rolled back and the workspace is not created.
+    pub async fn create(
+        &self,
+        name: &str,
+        alias_name: Option<&str>,
+        jobs: usize,
+        atomic: bool,
+    ) -> Result<()> {
         let safe_name = sanitize_name(name);
         let workspace_path = workspaces_dir().join(format!("{}.json", safe_name));
 
@@ -60,39 +142,101 @@ impl WorkspaceManager {
 
         let workspace_base = workspaces_dir().join(&safe_name);
         std::fs::create_dir_all(&workspace_base)?;
+        debug!(workspace = %name, ?projects, jobs, atomic, "creating workspace");
 
-        let mut created_worktrees = Vec::new();
+        let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+        let mut handles = Vec::new();
 
         for project in &projects {
+            let permit = semaphore.clone().acquire_owned().await?;
             let manager = WorktreeManager::new(project.clone(), workspace_base.clone());
-            match manager.create_workspace_worktree(name, None) {
+            let branch = name.to_string();
+            // Spans don't follow tasks onto the blocking pool on their own
+            let span = debug_span!("workspace_project", project = %project.display());
+            handles.push(tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                let _span = span.enter();
+                manager.create_workspace_worktree(&branch, None)
+            }));
+        }
+
+        let total = projects.len();
+        let mut created = Vec::new();
+        let mut failed = Vec::new();
+
+        for (project, handle) in projects.iter().zip(handles) {
+            // A panicked task is one more failed project, so --atomic still rolls back
+            let result = handle
+                .await
+                .unwrap_or_else(|e| Err(anyhow::anyhow!("worktree task failed: {}", e)));
+            match result {
                 Ok(info) => {
                     eprintln!(
-                        "Created worktree for {} at {}",
+                        "[{}/{}] Created worktree for {} at {}",
+                        created.len() + failed.len() + 1,
+                        total,
                         project.display(),
                         info.path.display()
                     );
-                    created_worktrees.push(project.clone());
+                    created.push((project.clone(), info));
                 }
                 Err(e) => {
                     eprintln!(
-                        "Warning: Failed to create worktree for {}: {}",
+                        "[{}/{}] Failed to create worktree for {}: {}",
+                        created.len() + failed.len() + 1,
+                        total,
                         project.display(),
                         e
                     );
-                    // Continue with other projects
+                    failed.push(project.clone());
                 }
             }
         }
 
-        if created_worktrees.is_empty() {
+        if atomic && !failed.is_empty() {
+            for (project, info) in &created {
+                let manager = WorktreeManager::new(project.clone(), workspace_base.clone());
+                match manager.rollback_worktree(info) {
+                    Ok(()) => eprintln!("Rolled back worktree for {}", project.display()),
+                    Err(e) => eprintln!(
+                        "Warning: Failed to roll back {}: {} (remove it manually)",
+                        info.path.display(),
+                        e
+                    ),
+                }
+            }
+            let _ = std::fs::remove_dir(&workspace_base);
+            anyhow::bail!(
+                "Workspace '{}' not created: failed for {}",
+                name,
+                failed
+                    .iter()
+                    .map(|project| project.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        if created.is_empty() {
             anyhow::bail!("Failed to create any worktrees");
         }
+        if !failed.is_empty() {
+            eprintln!(
+                "Created {} of {} worktrees; the workspace excludes the failed projects.",
+                created.len(),
+                total
+            );
+        }
+        let (projects, worktrees) = created
+            .into_iter()
+            .map(|(project, info)| (project, info.path))
+            .unzip();
 
         let workspace_info = WorkspaceInfo {
             name: name.to_string(),
             safe_name: safe_name.clone(),
-            projects: created_worktrees,
+            projects,
+            worktrees,
             alias: alias_name.map(String::from),
             created_at: Utc::now(),
         };
@@ -104,56 +248,163 @@ impl WorkspaceManager {
         Ok(())
     }
 
-    pub fn remove(&self, name: &str, force: bool) -> Result<()> {
-        let safe_name = sanitize_name(name);
-        let workspace_meta_path = workspaces_dir().join(format!("{}.json", safe_name));
-        let workspace_base = workspaces_dir().join(&safe_name);
+    pub fn load(&self, name: &str) -> Result<WorkspaceInfo> {
+        let workspace_meta_path = workspaces_dir().join(format!("{}.json", sanitize_name(name)));
 
         if !workspace_meta_path.exists() {
             anyhow::bail!("Workspace '{}' not found", name);
         }
 
         let content = std::fs::read_to_string(&workspace_meta_path)?;
-        let workspace_info: WorkspaceInfo = serde_json::from_str(&content)?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", workspace_meta_path.display()))
+    }
+
+    /// The worktree directory of each project in the workspace, paired with its project.
+    /// Paths are recomputed from the project name only for workspace files that predate
+    /// recording them, since the name can change as other projects come and go.
+    fn member_worktrees(&self, workspace_info: &WorkspaceInfo) -> Result<Vec<(PathBuf, PathBuf)>> {
+        if workspace_info.worktrees.len() == workspace_info.projects.len() {
+            return Ok(workspace_info
+                .projects
+                .iter()
+                .cloned()
+                .zip(workspace_info.worktrees.iter().cloned())
+                .collect());
+        }
+        let workspace_base = workspaces_dir().join(&workspace_info.safe_name);
+        workspace_info
+            .projects
+            .iter()
+            .map(|project| {
+                let manager = WorktreeManager::new(project.clone(), workspace_base.clone());
+                Ok((
+                    project.clone(),
+                    workspace_base.join(manager.get_project_name()?),
+                ))
+            })
+            .collect()
+    }
+
+    /// Remove every worktree of the workspace, then its metadata. If some removals fail,
+    /// the metadata is rewritten to list only the projects whose worktrees remain, so the
+    /// command can be re-run to finish. Worktrees already gone count as removed.
+    pub fn remove(&self, name: &str, force: bool) -> Result<()> {
+        let safe_name = sanitize_name(name);
+        let workspace_meta_path = workspaces_dir().join(format!("{}.json", safe_name));
+        let workspace_base = workspaces_dir().join(&safe_name);
+        let mut workspace_info = self.load(name)?;
+
+        let mut remaining = Vec::new();
 
-        let mut had_errors = false;
+        for (project, worktree_path) in self.member_worktrees(&workspace_info)? {
+            if !worktree_path.exists() {
+                eprintln!("Worktree for {} already removed", project.display());
+                continue;
+            }
 
-        for project in &workspace_info.projects {
-            let project_name = project
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("project");
-            let worktree_path = workspace_base.join(project_name);
             let manager = WorktreeManager::new(project.clone(), workspace_base.clone());
+            debug!(project = %project.display(), path = %worktree_path.display(), "removing workspace worktree");
 
             match manager.remove_worktree_at_path(&worktree_path, &workspace_info.name, force) {
                 Ok(_) => {
                     eprintln!("Removed worktree for {}", project.display());
                 }
+                // A failing strict hook runs after the worktree is already gone
+                Err(e) if !worktree_path.exists() => {
+                    eprintln!("Removed worktree for {}, but: {:#}", project.display(), e);
+                }
                 Err(e) => {
                     eprintln!(
                         "Warning: Failed to remove worktree for {}: {}",
                         project.display(),
                         e
                     );
-                    had_errors = true;
+                    remaining.push((project, worktree_path));
                 }
             }
         }
 
+        if !remaining.is_empty() {
+            let count = remaining.len();
+            (workspace_info.projects, workspace_info.worktrees) = remaining.into_iter().unzip();
+            let content = serde_json::to_string_pretty(&workspace_info)?;
+            std::fs::write(&workspace_meta_path, content)?;
+            anyhow::bail!(
+                "{} worktrees of workspace '{}' could not be removed; fix the problems above and run `maokai workspace remove {}` again",
+                count,
+                name,
+                name
+            );
+        }
+
         std::fs::remove_file(&workspace_meta_path)?;
         let _ = std::fs::remove_dir(&workspace_base);
+        eprintln!("Workspace '{}' removed.", name);
 
-        if had_errors {
-            eprintln!(
-                "Workspace '{}' removed with some errors (see warnings above).",
-                name
-            );
+        Ok(())
+    }
+
+    /// Run `command` in every worktree of the workspace, under a header naming the
+    /// project. With `parallel`, all commands run at once and their output is buffered
+    /// and printed in project order. Returns the number of commands that failed.
+    pub async fn exec(&self, name: &str, command: &[String], parallel: bool) -> Result<usize> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("No command given"))?;
+        let workspace_info = self.load(name)?;
+        let members = self.member_worktrees(&workspace_info)?;
+        debug!(workspace = %name, ?command, parallel, members = members.len(), "running workspace command");
+
+        let mut failed = 0;
+
+        if parallel {
+            let mut handles = Vec::new();
+            for (_, worktree_path) in &members {
+                let mut cmd = Command::new(program);
+                cmd.args(args).current_dir(worktree_path);
+                handles.push(tokio::task::spawn_blocking(move || cmd.output()));
+            }
+
+            for ((project, worktree_path), handle) in members.iter().zip(handles) {
+                print_exec_header(project, worktree_path);
+                match handle.await? {
+                    Ok(output) => {
+                        std::io::stdout().write_all(&output.stdout)?;
+                        std::io::stderr().write_all(&output.stderr)?;
+                        if !output.status.success() {
+                            report_exec_failure(output.status);
+                            failed += 1;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to run '{}': {}", program, e);
+                        failed += 1;
+                    }
+                }
+            }
         } else {
-            eprintln!("Workspace '{}' removed.", name);
+            for (project, worktree_path) in &members {
+                print_exec_header(project, worktree_path);
+                match Command::new(program)
+                    .args(args)
+                    .current_dir(worktree_path)
+                    .status()
+                {
+                    Ok(status) if status.success() => {}
+                    Ok(status) => {
+                        report_exec_failure(status);
+                        failed += 1;
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to run '{}': {}", program, e);
+                        failed += 1;
+                    }
+                }
+            }
         }
 
-        Ok(())
+        Ok(failed)
     }
 
     pub fn list(&self) -> Result<Vec<WorkspaceInfo>> {
@@ -174,7 +425,7 @@ impl WorkspaceManager {
             }
         }
 
-        workspaces.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        workspaces.sort_by_key(|wt| std::cmp::Reverse(wt.created_at));
         Ok(workspaces)
     }
 
@@ -188,6 +439,9 @@ impl WorkspaceManager {
 projects:
 #  - /path/to/your/first/project
 #  - /path/to/your/second/project
+
+# Optionally add every git repository matching a glob:
+# glob: ~/work/*/
 "#;
 
         std::fs::write(&temp_file, template)?;
@@ -196,28 +450,52 @@ projects:
         let content =
             std::fs::read_to_string(&temp_file).context("Failed to read workspace config")?;
 
-        #[derive(Deserialize)]
-        struct TempConfig {
-            projects: Vec<PathBuf>,
-        }
+        let config: WorkspaceFile =
+            parse_project_file(&temp_file, &content).context("Failed to parse workspace config")?;
 
-        let config: TempConfig =
-            serde_yaml::from_str(&content).context("Failed to parse workspace config")?;
+        // Validate explicitly listed projects; glob matches are git repos already
+        let projects = validate_projects(&config.projects)?;
+        collect_projects(&projects, config.glob.as_deref())
+    }
+}
 
-        // Validate projects
-        for project in &config.projects {
-            if !project.exists() {
-                anyhow::bail!("Project path does not exist: {}", project.display());
-            }
-            let git_path = project.join(".git");
-            if !git_path.exists() {
-                anyhow::bail!(
-                    "Project path is not a git repository: {}",
-                    project.display()
-                );
-            }
-        }
+/// The project list edited by `workspace create` without an alias.
+#[derive(Debug, Deserialize)]
+struct WorkspaceFile {
+    #[serde(default)]
+    projects: Vec<PathBuf>,
+    glob: Option<String>,
+}
+
+fn print_exec_header(project: &Path, worktree_path: &Path) {
+    eprintln!("==> {} ({})", project.display(), worktree_path.display());
+}
 
-        Ok(config.projects)
+fn report_exec_failure(status: ExitStatus) {
+    match status.code() {
+        Some(code) => eprintln!("Command exited with code {}", code),
+        None => eprintln!("Command was terminated by a signal"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn existing_workspace_yaml_parses() {
+        let path = Path::new("ws.yml");
+        let content =
+            "# Maokai Workspace\nprojects:\n  - /src/api\n  - /src/web\nglob: ~/work/*/\n";
+        let config: WorkspaceFile = parse_project_file(path, content).unwrap();
+        assert_eq!(
+            config.projects,
+            [PathBuf::from("/src/api"), PathBuf::from("/src/web")]
+        );
+        assert_eq!(config.glob.as_deref(), Some("~/work/*/"));
+
+        let config: WorkspaceFile = parse_project_file(path, "projects:\n  - /src/api\n").unwrap();
+        assert_eq!(config.projects, [PathBuf::from("/src/api")]);
+        assert_eq!(config.glob, None);
     }
 }
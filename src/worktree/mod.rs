@@ -1,10 +1,23 @@
 use anyhow::{Context, Result};
+use clap::ValueEnum;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output};
+use std::sync::{Once, OnceLock};
+use tracing::{debug, instrument};
 use uuid::Uuid;
 
-use crate::config::worktrees_registry_path;
+use crate::config::{
+    ProjectNameStrategy, expand_path, get_worktree_base_path, git_program, load_config,
+    load_repo_config, trusted_repo_command, workspaces_dir, worktrees_registry_path,
+};
+use crate::events::{self, Event};
+use crate::ui;
+use crate::workspace::editor::open_in_editor;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WorktreeInfo {
@@ -16,6 +29,103 @@ pub struct WorktreeInfo {
     pub agent: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub status: WorktreeStatus,
+    /// Branch the worktree's branch was created from (None if it already existed or
+    /// for entries recorded before this was tracked)
+    #[serde(default)]
+    pub base_branch: Option<String>,
+    /// Name of the worktree directory. Branch names are sanitized lossily (`a/b` and
+    /// `a-b` share a directory name), so this is stored rather than derived. Filled in
+    /// from `path` for entries recorded before it existed.
+    #[serde(default)]
+    pub worktree_dir_name: String,
+    /// Remote the branch was pushed to by `create --push`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pushed_to: Option<String>,
+    /// URL (or `#<number>`) of the issue the worktree was created for by `create --from-issue`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+}
+
+impl WorktreeInfo {
+    /// Environment variables describing this worktree, as set for custom commands and
+    /// printed by `maokai env`.
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("MAOKAI_WORKTREE_PATH", self.path.display().to_string()),
+            ("MAOKAI_BRANCH", self.branch.clone()),
+            ("MAOKAI_AGENT", self.agent.clone()),
+            ("MAOKAI_PROJECT_NAME", self.project_name.clone()),
+            ("MAOKAI_WORKTREE_ID", self.id.clone()),
+        ]
+    }
+}
+
+/// Everything known about one worktree, as printed by `info --json`.
+#[derive(Debug, Serialize)]
+pub struct WorktreeInfoReport<'a> {
+    #[serde(flatten)]
+    pub worktree: &'a WorktreeInfo,
+    /// None when the worktree directory no longer exists
+    pub git_state: Option<&'a GitState>,
+    pub disk_usage_bytes: u64,
+}
+
+/// One entry of `status --json`: the registry entry plus a summary of its git state.
+#[derive(Debug, Serialize)]
+pub struct WorktreeStatusReport {
+    #[serde(flatten)]
+    pub worktree: WorktreeInfo,
+    /// None when the worktree directory no longer exists
+    pub git_state: Option<GitSummary>,
+}
+
+/// The parts of [`GitState`] a dashboard needs at a glance.
+#[derive(Debug, Serialize, Clone)]
+pub struct GitSummary {
+    pub current_branch: Option<String>,
+    pub dirty: bool,
+    /// Number of paths with staged, unstaged, or untracked changes
+    pub changed_files: usize,
+    /// Tracking branch, e.g. `origin/foo`; ahead/behind are relative to it
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    /// Lock reason (empty if none was given) when the worktree is locked
+    pub locked: Option<String>,
+}
+
+impl From<&GitState> for GitSummary {
+    fn from(state: &GitState) -> Self {
+        Self {
+            current_branch: state.branch.clone(),
+            dirty: state.is_dirty(),
+            changed_files: state.dirty_files.len(),
+            upstream: state.upstream.clone(),
+            ahead: state.ahead,
+            behind: state.behind,
+            locked: state.locked.clone(),
+        }
+    }
+}
+
+/// Live git state of a worktree checkout.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct GitState {
+    /// Checked-out branch, or None when HEAD is detached
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    /// Paths with staged, unstaged, or untracked changes
+    pub dirty_files: Vec<String>,
+    /// Lock reason (empty if none was given) when the worktree is locked
+    pub locked: Option<String>,
+}
+
+impl GitState {
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty_files.is_empty()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -30,6 +140,104 @@ struct WorktreeRegistry {
     worktrees: Vec<WorktreeInfo>,
 }
 
+/// Field to order worktree listings by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    #[default]
+    Created,
+    Branch,
+    Project,
+    /// Time of the last commit on the worktree's checkout
+    Activity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortBy {
+    /// Newest first for times, alphabetical for names.
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            SortBy::Created | SortBy::Activity => SortOrder::Desc,
+            SortBy::Branch | SortBy::Project => SortOrder::Asc,
+        }
+    }
+}
+
+pub fn sort_worktrees(worktrees: &mut [WorktreeInfo], sort: SortBy, order: Option<SortOrder>) {
+    match sort {
+        SortBy::Created => worktrees.sort_by_key(|wt| wt.created_at),
+        SortBy::Branch => worktrees.sort_by(|a, b| a.branch.cmp(&b.branch)),
+        // Ties within a project are broken by branch so the output is stable
+        SortBy::Project => worktrees.sort_by(|a, b| {
+            a.project_name
+                .cmp(&b.project_name)
+                .then_with(|| a.branch.cmp(&b.branch))
+        }),
+        // Worktrees without a readable commit time (e.g. missing directories) count as oldest
+        SortBy::Activity => {
+            let times = last_commit_times(worktrees);
+            worktrees.sort_by_key(|wt| times.get(&wt.path).copied().flatten());
+        }
+    }
+
+    if order.unwrap_or(sort.default_order()) == SortOrder::Desc {
+        worktrees.reverse();
+    }
+}
+
+/// Unix time of the last commit in each worktree, keyed by path. Git is queried once per
+/// worktree, concurrently.
+fn last_commit_times(worktrees: &[WorktreeInfo]) -> HashMap<PathBuf, Option<i64>> {
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = worktrees
+            .iter()
+            .map(|wt| {
+                scope.spawn(move || {
+                    let output = run_git(&wt.path, ["log", "-1", "--format=%ct"]).ok()?;
+                    if !output.status.success() {
+                        return None;
+                    }
+                    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+                })
+            })
+            .collect();
+        worktrees
+            .iter()
+            .zip(handles)
+            .map(|(wt, handle)| {
+                let time = handle.join().expect("git log thread panicked");
+                (wt.path.clone(), time)
+            })
+            .collect()
+    })
+}
+
+/// Optional settings for creating a worktree. The default bases a new branch on the
+/// current branch with no extra post-create steps.
+#[derive(Debug, Default, Clone)]
+pub struct CreateOptions {
+    /// Branch to create the new branch from (defaults to the current branch)
+    pub base_branch: Option<String>,
+    /// Make an empty commit with this message on a newly created branch
+    pub initial_commit: Option<String>,
+    /// Base the new branch on this remote's copy of the base branch and track it
+    pub remote: Option<String>,
+    /// Fetch the remote base branch first so the worktree starts from its latest state
+    pub fetch: bool,
+    /// Use the first free `<name>-2`, `<name>-3`, ... if the worktree directory exists
+    pub rename_on_conflict: bool,
+    /// Nest the worktree under a directory named after its agent (`<base>/<agent>/<name>`)
+    pub per_agent_dir: bool,
+    /// Issue reference recorded in the registry entry
+    pub issue: Option<String>,
+    /// Create the worktree exactly here instead of `<base>/<project>-<branch>`
+    pub path: Option<PathBuf>,
+}
+
 pub struct WorktreeManager {
     project_root: PathBuf,
     base_path: PathBuf,
@@ -47,26 +255,191 @@ impl WorktreeManager {
         self.project_root.join(".git").exists()
     }
 
-    /// List all worktrees from the central registry.
-    /// Optionally filters by project_root matching the current manager's project_root.
+    /// List all worktrees from the central registry, newest first.
     pub fn list_all_worktrees(&self) -> Result<Vec<WorktreeInfo>> {
-        let mut all_worktrees = load_registry()?;
+        self.list_all_worktrees_sorted(SortBy::Created, None)
+    }
 
-        // Sort by creation time (newest first)
-        all_worktrees.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    /// List all worktrees from the central registry in the given order. `order` defaults
+    /// to the natural direction of `sort` (see [`SortBy::default_order`]).
+    pub fn list_all_worktrees_sorted(
+        &self,
+        sort: SortBy,
+        order: Option<SortOrder>,
+    ) -> Result<Vec<WorktreeInfo>> {
+        let mut all_worktrees = load_registry()?;
+        sort_worktrees(&mut all_worktrees, sort, order);
         Ok(all_worktrees)
     }
 
+    /// Like [`Self::list_worktrees`], but ordered by `sort`/`order`.
+    pub fn list_worktrees_sorted(
+        &self,
+        sort: SortBy,
+        order: Option<SortOrder>,
+    ) -> Result<Vec<WorktreeInfo>> {
+        let mut worktrees = self.list_worktrees()?;
+        sort_worktrees(&mut worktrees, sort, order);
+        Ok(worktrees)
+    }
+
+    /// This project's registry entries, without asking git which worktrees still exist.
+    /// Entries for worktrees removed outside maokai are included.
+    pub fn list_registered_worktrees(&self) -> Result<Vec<WorktreeInfo>> {
+        let mut worktrees = load_registry()?;
+        worktrees.retain(|info| info.project_root == self.project_root);
+        Ok(worktrees)
+    }
+
     pub fn create_worktree(
         &self,
         branch: &str,
         agent: &str,
-        base_branch: Option<&str>,
+        options: &CreateOptions,
     ) -> Result<WorktreeInfo> {
-        let project_name = self.get_project_name()?;
-        let safe_branch_name = self.sanitize_branch_name(branch);
-        let worktree_name = format!("{}-{}", project_name, safe_branch_name);
-        self.create_worktree_at(&worktree_name, branch, agent, base_branch)
+        let worktree_path = match &options.path {
+            Some(path) => self.validate_explicit_path(path)?,
+            None => self.get_worktree_path(branch, agent, options.per_agent_dir)?,
+        };
+        self.create_worktree_at(worktree_path, branch, agent, options)
+    }
+
+    /// Check a `create --path` location: it must not exist or be registered yet, and its
+    /// parent must be writable. Returns the path made canonical (relative paths are taken
+    /// from the current directory), matching how git reports worktree paths.
+    fn validate_explicit_path(&self, path: &Path) -> Result<PathBuf> {
+        if path.exists() {
+            anyhow::bail!("Worktree path already exists: {}", path.display());
+        }
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            anyhow::bail!("Invalid worktree path: {}", path.display());
+        };
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+        tempfile::tempfile_in(parent)
+            .with_context(|| format!("{} is not writable", parent.display()))?;
+        let path = parent.canonicalize()?.join(name);
+
+        if let Some(existing) = load_registry()?.into_iter().find(|wt| wt.path == path) {
+            anyhow::bail!(
+                "{} is already registered as the worktree for '{}'",
+                path.display(),
+                existing.branch
+            );
+        }
+        Ok(path)
+    }
+
+    /// Copy the project checkout's uncommitted changes to tracked files (`git diff HEAD`,
+    /// staged or not, binary files included) into the worktree, leaving the checkout
+    /// as it is. Returns false if there were no changes. `git apply` is all or nothing,
+    /// so on failure the worktree stays clean and the patch is kept for manual use.
+    pub fn snapshot_changes(&self, worktree_path: &Path) -> Result<bool> {
+        let output = run_git(&self.project_root, ["diff", "HEAD", "--binary"])
+            .context("Failed to diff uncommitted changes")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to diff uncommitted changes: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        if output.stdout.is_empty() {
+            return Ok(false);
+        }
+
+        let mut patch = tempfile::Builder::new()
+            .prefix("maokai-snapshot-")
+            .suffix(".patch")
+            .tempfile()
+            .context("Failed to create patch file")?;
+        patch.write_all(&output.stdout)?;
+
+        let output = run_git(
+            worktree_path,
+            [OsStr::new("apply"), patch.path().as_os_str()],
+        )
+        .context("Failed to apply changes")?;
+        if !output.status.success() {
+            let (_, kept) = patch.keep().context("Failed to keep patch file")?;
+            anyhow::bail!(
+                "Failed to apply changes in the new worktree (it was left clean; the patch is saved at {}): {}",
+                kept.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(true)
+    }
+
+    /// Create a worktree and move the current uncommitted changes (including untracked
+    /// files) into it. The changes are stashed before creation and applied in the new
+    /// worktree; on any failure the stash is popped back into the original checkout.
+    pub fn create_worktree_with_changes(
+        &self,
+        branch: &str,
+        agent: &str,
+        options: &CreateOptions,
+    ) -> Result<WorktreeInfo> {
+        if !self.has_uncommitted_changes()? {
+            eprintln!("No uncommitted changes to move, creating a clean worktree.");
+            return self.create_worktree(branch, agent, options);
+        }
+
+        let message = format!("maokai: move changes to {}", branch);
+        let output = run_git(
+            &self.project_root,
+            ["stash", "push", "--include-untracked", "-m", &message],
+        )
+        .context("Failed to stash changes")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to stash changes: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let worktree_info = match self.create_worktree(branch, agent, options) {
+            Ok(info) => info,
+            Err(e) => {
+                if let Err(restore_err) = self.restore_stash() {
+                    return Err(e.context(format!("{:#}", restore_err)));
+                }
+                return Err(e);
+            }
+        };
+
+        let output = run_git(&worktree_info.path, ["stash", "apply", "stash@{0}"])
+            .context("Failed to apply stashed changes")?;
+
+        if !output.status.success() {
+            // Undo the partial apply so the worktree is left clean
+            let _ = run_git(&worktree_info.path, ["reset", "--hard", "--quiet"]);
+            let _ = run_git(&worktree_info.path, ["clean", "-fd", "--quiet"]);
+            if let Err(restore_err) = self.restore_stash() {
+                return Err(anyhow::anyhow!(
+                    "Failed to apply changes in new worktree: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                )
+                .context(format!("{:#}", restore_err)));
+            }
+            anyhow::bail!(
+                "Failed to apply changes in new worktree (changes restored to {}): {}",
+                self.project_root.display(),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let _ = run_git(
+            &self.project_root,
+            ["stash", "drop", "--quiet", "stash@{0}"],
+        );
+
+        Ok(worktree_info)
     }
 
     pub fn create_workspace_worktree(
@@ -75,29 +448,71 @@ impl WorktreeManager {
         base_branch: Option<&str>,
     ) -> Result<WorktreeInfo> {
         let project_name = self.get_project_name()?;
-        self.create_worktree_at(&project_name, branch, "none", base_branch)
+        let options = CreateOptions {
+            base_branch: base_branch.map(String::from),
+            ..Default::default()
+        };
+        self.create_worktree_at(self.base_path.join(project_name), branch, "none", &options)
     }
 
+    #[instrument(level = "debug", skip(self, options))]
     fn create_worktree_at(
         &self,
-        worktree_name: &str,
+        mut worktree_path: PathBuf,
         branch: &str,
         agent: &str,
-        base_branch: Option<&str>,
+        options: &CreateOptions,
     ) -> Result<WorktreeInfo> {
         let project_name = self.get_project_name()?;
-        let worktree_path = self.base_path.join(worktree_name);
-        std::fs::create_dir_all(&self.base_path)
-            .context("Failed to create base worktree directory")?;
+        if worktree_path.exists() {
+            if !options.rename_on_conflict {
+                anyhow::bail!(
+                    "Worktree directory already exists: {} (use --rename-on-conflict to pick a new name)",
+                    worktree_path.display()
+                );
+            }
+            let original = worktree_path.clone();
+            let file_name = dir_name_of(&original);
+            worktree_path = (2..)
+                .map(|n| original.with_file_name(format!("{}-{}", file_name, n)))
+                .find(|candidate| !candidate.exists())
+                .expect("unbounded range always yields a free name");
+            eprintln!(
+                "{} already exists, using {}",
+                original.display(),
+                worktree_path.display()
+            );
+        }
+        if let Some(parent) = worktree_path.parent() {
+            std::fs::create_dir_all(parent).context("Failed to create base worktree directory")?;
+        }
 
-        let base = match base_branch {
-            Some(base) => base.to_string(),
+        let mut base = match &options.base_branch {
+            Some(base) => base.clone(),
             _ => self.get_current_branch()?,
         };
 
         // Check if branch exists
         let branch_exists = self.branch_exists(branch)?;
 
+        if branch_exists {
+            if let Some(remote) = &options.remote {
+                eprintln!(
+                    "Branch '{}' already exists, ignoring --remote={}.",
+                    branch, remote
+                );
+            }
+        } else if let Some(remote) = &options.remote {
+            base = self.resolve_remote_base(remote, &base, options.fetch)?;
+        } else {
+            if options.fetch {
+                self.fetch_base(&base)?;
+            }
+            if options.base_branch.is_some() {
+                self.validate_base(&base)?;
+            }
+        }
+
         let mut args = vec!["worktree", "add"];
 
         if branch_exists {
@@ -106,17 +521,16 @@ impl WorktreeManager {
             args.push(branch);
         } else {
             // If branch doesn't exist, create it with -b flag
+            if options.remote.is_some() {
+                args.push("--track");
+            }
             args.push("-b");
             args.push(branch);
             args.push(worktree_path.to_str().unwrap());
             args.push(&base);
         }
 
-        let output = Command::new("git")
-            .args(&args)
-            .current_dir(&self.project_root)
-            .output()
-            .context("Failed to create git worktree")?;
+        let output = run_git(&self.project_root, &args).context("Failed to create git worktree")?;
 
         if !output.status.success() {
             anyhow::bail!(
@@ -126,6 +540,7 @@ impl WorktreeManager {
         }
 
         let worktree_info = WorktreeInfo {
+            worktree_dir_name: dir_name_of(&worktree_path),
             id: Uuid::new_v4().to_string(),
             branch: branch.to_string(),
             path: worktree_path,
@@ -134,170 +549,1005 @@ impl WorktreeManager {
             agent: agent.to_string(),
             created_at: chrono::Utc::now(),
             status: WorktreeStatus::Active,
+            base_branch: (!branch_exists).then_some(base),
+            pushed_to: None,
+            issue: options.issue.clone(),
         };
 
         add_to_registry(&worktree_info)?;
+        events::emit(Event::WorktreeCreated {
+            id: &worktree_info.id,
+            branch: &worktree_info.branch,
+            path: &worktree_info.path,
+            project_root: &worktree_info.project_root,
+        });
+        write_info_file(&worktree_info)?;
         self.copy_env_files(&worktree_info.path)?;
+        self.copy_configured_paths(&worktree_info.path)?;
+
+        if let Some(message) = &options.initial_commit {
+            if branch_exists {
+                eprintln!(
+                    "Branch '{}' already existed, skipping initial commit.",
+                    branch
+                );
+            } else {
+                self.make_initial_commit(&worktree_info.path, message)?;
+            }
+        }
+
         Ok(worktree_info)
     }
 
+    fn make_initial_commit(&self, worktree_path: &Path, message: &str) -> Result<()> {
+        let output = run_git(worktree_path, ["commit", "--allow-empty", "-m", message])
+            .context("Failed to create initial commit")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to create initial commit: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Copy `.env*` files from the project root into the worktree. With `copy_parent_env`
+    /// set, env files in every directory from the git root down to the project root are
+    /// copied too, each to the same relative location in the worktree.
     fn copy_env_files(&self, worktree_path: &Path) -> Result<()> {
-        for entry in std::fs::read_dir(&self.project_root)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                    if name.starts_with(".env") {
-                        let dest = worktree_path.join(name);
-                        std::fs::copy(&path, &dest)?;
-                    }
+        let ignore = self.load_copy_ignore()?;
+        let copy_dir = |dir: &Path, dest_dir: &Path| -> Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_file()
+                    && let Some(name) = path.file_name().and_then(|n| n.to_str())
+                    && name.starts_with(".env")
+                    && !ignore.matched(Path::new(name), false).is_ignore()
+                {
+                    std::fs::create_dir_all(dest_dir)?;
+                    let target = dest_dir.join(name);
+                    debug!(from = %path.display(), to = %target.display(), "copying env file");
+                    std::fs::copy(&path, target)?;
                 }
             }
+            Ok(())
+        };
+
+        let copy_parent_env = match load_repo_config(&self.project_root)?.copy_parent_env {
+            Some(enabled) => enabled,
+            None => load_config()?.copy_parent_env,
+        };
+        if !copy_parent_env {
+            return copy_dir(&self.project_root, worktree_path);
+        }
+
+        let toplevel = self.git_toplevel()?;
+        let project_root = self.project_root.canonicalize()?;
+        let relative = project_root.strip_prefix(&toplevel).with_context(|| {
+            format!(
+                "Project root {} is outside the git root {}",
+                project_root.display(),
+                toplevel.display()
+            )
+        })?;
+
+        let mut dir = toplevel.clone();
+        let mut dest_dir = worktree_path.to_path_buf();
+        copy_dir(&dir, &dest_dir)?;
+        for component in relative.components() {
+            dir.push(component);
+            dest_dir.push(component);
+            copy_dir(&dir, &dest_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the files and directories listed under `copy` in `.maokai.toml` into the
+    /// worktree, at the same relative path. Directories are merged without overwriting
+    /// files that already exist (e.g. tracked ones); symlinks are recreated pointing at
+    /// the original target. A missing or failing entry only prints a warning.
+    fn copy_configured_paths(&self, worktree_path: &Path) -> Result<()> {
+        for relative in load_repo_config(&self.project_root)?.copy {
+            if let Err(e) = self.copy_configured_path(&relative, worktree_path) {
+                eprintln!("Warning: failed to copy {}: {:#}", relative.display(), e);
+            }
         }
         Ok(())
     }
 
-    /// List worktrees for this project by cross-referencing git worktree list with the registry.
-    /// Returns the intersection (validates worktrees still exist in git).
-    pub fn list_worktrees(&self) -> Result<Vec<WorktreeInfo>> {
-        let output = Command::new("git")
-            .args(["worktree", "list", "--porcelain"])
-            .current_dir(&self.project_root)
-            .output()
-            .context("Failed to list git worktrees")?;
+    fn copy_configured_path(&self, relative: &Path, worktree_path: &Path) -> Result<()> {
+        if relative
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            anyhow::bail!("`copy` entries must be relative paths inside the project");
+        }
+        let source = self.project_root.join(relative);
+        let target = worktree_path.join(relative);
+        let metadata = std::fs::symlink_metadata(&source)
+            .with_context(|| format!("{} not found", source.display()))?;
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        if metadata.is_symlink() {
+            if target.symlink_metadata().is_ok() {
+                return Ok(());
+            }
+            // Resolved so relative links keep pointing at the same place
+            let link_target = source.canonicalize()?;
+            debug!(from = %source.display(), to = %target.display(), "linking configured path");
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&link_target, &target)?;
+            #[cfg(windows)]
+            {
+                if link_target.is_dir() {
+                    std::os::windows::fs::symlink_dir(&link_target, &target)?;
+                } else {
+                    std::os::windows::fs::symlink_file(&link_target, &target)?;
+                }
+            }
+        } else if metadata.is_dir() {
+            copy_dir_recursive(&source, &target, false)?;
+        } else if !target.exists() {
+            debug!(from = %source.display(), to = %target.display(), "copying configured file");
+            std::fs::copy(&source, &target)?;
+        }
+        Ok(())
+    }
+
+    /// Root of the git checkout containing the project (`git rev-parse --show-toplevel`).
+    fn git_toplevel(&self) -> Result<PathBuf> {
+        let output = run_git(&self.project_root, ["rev-parse", "--show-toplevel"])
+            .context("Failed to find the git root")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to find the git root: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        let toplevel = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(PathBuf::from(toplevel).canonicalize()?)
+    }
+
+    /// Seed a worktree with the contents of a template directory. Files that already
+    /// exist in the worktree (e.g. tracked files) are kept unless `overwrite` is set.
+    /// Returns the number of files copied.
+    pub fn apply_template(
+        &self,
+        template: &Path,
+        worktree_path: &Path,
+        overwrite: bool,
+    ) -> Result<usize> {
+        if !template.is_dir() {
+            anyhow::bail!("Template directory not found: {}", template.display());
+        }
+        copy_dir_recursive(template, worktree_path, overwrite)
+            .with_context(|| format!("Failed to copy template from {}", template.display()))
+    }
+
+    /// Copy git-ignored files (local settings, secrets, ...) from the project root into
+    /// the worktree. Files matched by `.maokaiignore`, files already present in the
+    /// worktree and files larger than [`COPY_IGNORED_MAX_SIZE`] are skipped. Returns the
+    /// number of files copied and the paths skipped for size.
+    pub fn copy_ignored_files(&self, worktree_path: &Path) -> Result<(usize, Vec<PathBuf>)> {
+        let output = run_git(
+            &self.project_root,
+            [
+                "ls-files",
+                "-z",
+                "--others",
+                "--ignored",
+                "--exclude-standard",
+            ],
+        )
+        .context("Failed to list ignored files")?;
 
         if !output.status.success() {
-            return Ok(vec![]);
+            anyhow::bail!(
+                "Failed to list ignored files: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
         }
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let mut git_worktree_paths: Vec<PathBuf> = Vec::new();
+        let ignore = self.load_copy_ignore()?;
+        let mut copied = 0;
+        let mut too_large = Vec::new();
 
-        for chunk in output_str.split("\n\n") {
-            if chunk.trim().is_empty() {
+        for relative in String::from_utf8_lossy(&output.stdout).split('\0') {
+            if relative.is_empty() {
                 continue;
             }
 
-            for line in chunk.lines() {
-                if line.starts_with("worktree ") {
-                    if let Some(path) = line.strip_prefix("worktree ") {
-                        git_worktree_paths.push(PathBuf::from(path));
-                    }
-                }
+            let source = self.project_root.join(relative);
+            let target = worktree_path.join(relative);
+            let Ok(metadata) = std::fs::symlink_metadata(&source) else {
+                continue;
+            };
+            if !metadata.is_file()
+                || target.exists()
+                || ignore
+                    .matched_path_or_any_parents(&source, false)
+                    .is_ignore()
+            {
+                continue;
+            }
+            if metadata.len() > COPY_IGNORED_MAX_SIZE {
+                too_large.push(PathBuf::from(relative));
+                continue;
             }
+
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            debug!(from = %source.display(), to = %target.display(), "copying ignored file");
+            std::fs::copy(&source, &target)
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+            copied += 1;
+        }
+
+        Ok((copied, too_large))
+    }
+
+    /// Load `.maokaiignore` (gitignore syntax) from the project root. Files it matches
+    /// are never copied into new worktrees. An empty matcher is returned if absent.
+    fn load_copy_ignore(&self) -> Result<Gitignore> {
+        let ignore_path = self.project_root.join(".maokaiignore");
+        if !ignore_path.exists() {
+            return Ok(Gitignore::empty());
+        }
+
+        let mut builder = GitignoreBuilder::new(&self.project_root);
+        if let Some(e) = builder.add(&ignore_path) {
+            return Err(e).context("Failed to read .maokaiignore");
         }
+        builder.build().context("Failed to parse .maokaiignore")
+    }
+
+    /// List worktrees for this project by cross-referencing git worktree list with the registry.
+    /// Returns the intersection (validates worktrees still exist in git).
+    pub fn list_worktrees(&self) -> Result<Vec<WorktreeInfo>> {
+        let git_worktree_paths = git_worktree_paths(&self.project_root)?;
 
         // Load registry and filter to worktrees that exist in git and match this project
         let registry = load_registry()?;
         let worktrees: Vec<WorktreeInfo> = registry
             .into_iter()
             .filter(|info| {
-                info.project_root == self.project_root
-                    && git_worktree_paths.contains(&info.path)
+                info.project_root == self.project_root && git_worktree_paths.contains(&info.path)
             })
             .collect();
 
         Ok(worktrees)
     }
 
-    pub fn remove_worktree(&self, branch: &str) -> Result<()> {
-        self.remove_worktree_with_options(branch, false)
-    }
-
-    pub fn remove_worktree_force(&self, branch: &str) -> Result<()> {
-        self.remove_worktree_with_options(branch, true)
+    /// Find a worktree by branch, in this project when inside a git repo, otherwise
+    /// across all projects (the same scope `ls` uses).
+    pub fn find_by_branch(&self, branch: &str) -> Result<Option<WorktreeInfo>> {
+        let worktrees = if self.is_git_repo() {
+            self.list_worktrees()?
+        } else {
+            self.list_all_worktrees()?
+        };
+        Ok(worktrees.into_iter().find(|wt| wt.branch == branch))
     }
 
-    fn remove_worktree_with_options(&self, branch: &str, force: bool) -> Result<()> {
-        // Find the worktree by branch name from existing worktrees
+    /// Find a worktree by its directory name (e.g. `myproject-feature-auth`), in the same
+    /// scope as [`Self::find_by_branch`].
+    pub fn find_by_dir_name(&self, dir_name: &str) -> Result<Option<WorktreeInfo>> {
         let worktrees = if self.is_git_repo() {
             self.list_worktrees()?
         } else {
             self.list_all_worktrees()?
         };
+        Ok(worktrees
+            .into_iter()
+            .find(|wt| wt.worktree_dir_name == dir_name))
+    }
 
-        let worktree_info = worktrees
-            .iter()
-            .find(|wt| wt.branch == branch)
-            .ok_or_else(|| anyhow::anyhow!("Worktree for branch '{}' not found", branch))?;
+    /// Find a worktree by its registry ID. IDs are unique, so every project is searched.
+    pub fn find_by_id(&self, id: &str) -> Result<Option<WorktreeInfo>> {
+        Ok(load_registry()?.into_iter().find(|wt| wt.id == id))
+    }
 
-        let mut args = vec!["worktree", "remove"];
-        if force {
-            args.push("--force");
+    /// Find a worktree by branch, falling back to its directory name.
+    pub fn find_worktree(&self, name: &str) -> Result<Option<WorktreeInfo>> {
+        match self.find_by_branch(name)? {
+            Some(wt) => Ok(Some(wt)),
+            None => self.find_by_dir_name(name),
         }
-        args.push(worktree_info.path.to_str().unwrap());
+    }
 
-        let output = Command::new("git")
-            .args(&args)
-            .current_dir(&self.project_root)
-            .output()
-            .context("Failed to remove git worktree")?;
+    /// [`GitSummary`] of each worktree, in order, read concurrently. None where the
+    /// worktree directory no longer exists.
+    pub fn git_summaries(&self, worktrees: &[WorktreeInfo]) -> Result<Vec<Option<GitSummary>>> {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = worktrees
+                .iter()
+                .map(|wt| scope.spawn(move || self.worktree_git_state(&wt.path)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    let state = handle.join().expect("git state thread panicked")?;
+                    Ok(state.as_ref().map(GitSummary::from))
+                })
+                .collect()
+        })
+    }
+
+    /// Read branch, upstream, ahead/behind and changed files of the checkout at `path`.
+    /// Returns None if the worktree directory no longer exists.
+    pub fn worktree_git_state(&self, path: &Path) -> Result<Option<GitState>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let output = run_git(path, ["status", "--porcelain=v2", "--branch"])
+            .context("Failed to read worktree status")?;
 
         if !output.status.success() {
             anyhow::bail!(
-                "Failed to remove worktree: {}",
+                "Failed to read worktree status: {}",
                 String::from_utf8_lossy(&output.stderr)
             );
         }
 
-        let _ = Command::new("git")
-            .args(["branch", "-D", branch])
-            .current_dir(&self.project_root)
-            .output();
+        let mut state = GitState::default();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if let Some(head) = line.strip_prefix("# branch.head ") {
+                state.branch = (head != "(detached)").then(|| head.to_string());
+            } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+                state.upstream = Some(upstream.to_string());
+            } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+                for part in ab.split_whitespace() {
+                    if let Some(n) = part.strip_prefix('+') {
+                        state.ahead = n.parse().unwrap_or(0);
+                    } else if let Some(n) = part.strip_prefix('-') {
+                        state.behind = n.parse().unwrap_or(0);
+                    }
+                }
+            } else if !line.starts_with('#') {
+                // Entry formats: "1 <7 fields> path", "2 <8 fields> path<TAB>orig",
+                // "u <9 fields> path", "? path"
+                let fields = match line.chars().next() {
+                    Some('1') => 9,
+                    Some('2') => 10,
+                    Some('u') => 11,
+                    _ => 2,
+                };
+                if let Some(path) = line.splitn(fields, ' ').last() {
+                    let path = path.split('\t').next().unwrap_or(path);
+                    state.dirty_files.push(path.to_string());
+                }
+            }
+        }
+        state.locked = lock_reason(path, path)?;
 
-        remove_from_registry(&worktree_info.path)?;
-        Ok(())
+        Ok(Some(state))
     }
 
-    pub fn remove_worktree_at_path(&self, path: &Path, branch: &str, force: bool) -> Result<()> {
-        let mut args = vec!["worktree", "remove"];
-        if force {
-            args.push("--force");
+    /// Lock the worktree at `path` with `git worktree lock`, so neither git nor maokai
+    /// removes it without `--force`.
+    pub fn lock_worktree(&self, path: &Path, reason: Option<&str>) -> Result<()> {
+        let mut args = vec!["worktree", "lock"];
+        if let Some(reason) = reason {
+            args.extend(["--reason", reason]);
         }
-        args.push(path.to_str().unwrap());
-
-        let output = Command::new("git")
-            .args(&args)
-            .current_dir(&self.project_root)
-            .output()
-            .context("Failed to remove git worktree")?;
+        let path_arg = path.to_str().unwrap();
+        args.push(path_arg);
 
+        let output = run_git(&self.project_root, &args).context("Failed to lock git worktree")?;
         if !output.status.success() {
             anyhow::bail!(
-                "Failed to remove worktree: {}",
-                String::from_utf8_lossy(&output.stderr)
+                "Failed to lock worktree: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
             );
         }
+        Ok(())
+    }
 
-        let _ = Command::new("git")
-            .args(["branch", "-D", branch])
-            .current_dir(&self.project_root)
-            .output();
-
-        remove_from_registry(path)?;
+    pub fn unlock_worktree(&self, path: &Path) -> Result<()> {
+        let output = run_git(
+            &self.project_root,
+            ["worktree", "unlock", path.to_str().unwrap()],
+        )
+        .context("Failed to unlock git worktree")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to unlock worktree: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
         Ok(())
     }
 
-    fn branch_exists(&self, branch: &str) -> Result<bool> {
-        let output = Command::new("git")
-            .args([
-                "show-ref",
-                "--verify",
-                "--quiet",
-                &format!("refs/heads/{}", branch),
-            ])
+    /// Rename the branch checked out in `wt` to `new_branch` with `git branch -m` and
+    /// record it in the registry. The worktree directory keeps its name. Returns the
+    /// updated entry.
+    pub fn rename_worktree(&self, wt: &WorktreeInfo, new_branch: &str) -> Result<WorktreeInfo> {
+        if self.branch_exists(new_branch)? {
+            anyhow::bail!("Branch '{}' already exists", new_branch);
+        }
+
+        let output = run_git(&wt.path, ["branch", "-m", &wt.branch, new_branch])
+            .context("Failed to rename branch")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to rename branch '{}' to '{}': {}",
+                wt.branch,
+                new_branch,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut renamed = wt.clone();
+        renamed.branch = new_branch.to_string();
+        if !update_registry_entry(&wt.id, |entry| entry.branch = renamed.branch.clone())? {
+            anyhow::bail!("Worktree with ID '{}' not found in the registry", wt.id);
+        }
+        if wt.path.join(INFO_FILE_NAME).exists() {
+            write_info_file(&renamed)?;
+        }
+        Ok(renamed)
+    }
+
+    pub fn remove_worktree(&self, branch: &str) -> Result<()> {
+        self.remove_worktree_with_options(branch, false)
+    }
+
+    pub fn remove_worktree_force(&self, branch: &str) -> Result<()> {
+        self.remove_worktree_with_options(branch, true)
+    }
+
+    #[instrument(level = "debug", skip(self))]
+    fn remove_worktree_with_options(&self, branch: &str, force: bool) -> Result<()> {
+        // Find the worktree by branch name from existing worktrees
+        let worktrees = if self.is_git_repo() {
+            self.list_worktrees()?
+        } else {
+            self.list_all_worktrees()?
+        };
+
+        let worktree_info = worktrees
+            .iter()
+            .find(|wt| wt.branch == branch)
+            .ok_or_else(|| anyhow::anyhow!("Worktree for branch '{}' not found", branch))?;
+
+        self.ensure_removable(&worktree_info.path, force)?;
+
+        // An untracked info file would otherwise make a non-forced remove fail
+        remove_info_file(&worktree_info.path);
+
+        self.git_worktree_remove(&worktree_info.path, force)?;
+
+        let _ = run_git(&self.project_root, ["branch", "-D", branch]);
+
+        // Clean up a per-agent directory once its last worktree is gone. Only directories
+        // maokai made directly under a base qualify; a `--path` parent is the user's.
+        if let Some(parent) = worktree_info.path.parent()
+            && let Some(grandparent) = parent.parent()
+            && (grandparent == self.base_path
+                || self.worktree_base().ok().as_deref() == Some(grandparent))
+        {
+            let _ = std::fs::remove_dir(parent);
+        }
+
+        remove_from_registry(&worktree_info.path)?;
+        events::emit(Event::WorktreeRemoved {
+            branch,
+            path: &worktree_info.path,
+        });
+        self.run_post_remove_hook(&worktree_info.path, branch)
+    }
+
+    /// Run the configured `post_remove` command (repo config first, then user config) in
+    /// the project root after a worktree is gone. A failing hook only warns unless
+    /// `post_remove_strict` is set; the worktree stays removed either way.
+    fn run_post_remove_hook(&self, path: &Path, branch: &str) -> Result<()> {
+        let repo_config = load_repo_config(&self.project_root)?;
+        let config = load_config()?;
+        let repo_command = trusted_repo_command(
+            &config,
+            &self.project_root,
+            "post_remove",
+            repo_config.post_remove,
+        );
+        let Some(command) = repo_command.or(config.post_remove) else {
+            return Ok(());
+        };
+        let strict = repo_config
+            .post_remove_strict
+            .unwrap_or(config.post_remove_strict);
+
+        debug!(%command, "running post_remove hook");
+        let result = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
             .current_dir(&self.project_root)
-            .output()
-            .context("Failed to check if branch exists")?;
+            .env("MAOKAI_BRANCH", branch)
+            .env("MAOKAI_WORKTREE_PATH", path)
+            .env("MAOKAI_PROJECT_ROOT", &self.project_root)
+            .stdin(std::process::Stdio::null())
+            .status()
+            .with_context(|| format!("Failed to run post_remove hook '{}'", command))
+            .and_then(|status| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "post_remove hook '{}' failed with exit code: {:?}",
+                        command,
+                        status.code()
+                    ))
+                }
+            });
+
+        match result {
+            Err(e) if !strict => {
+                eprintln!("Warning: {:#}", e);
+                Ok(())
+            }
+            result => result.with_context(|| {
+                format!("Removed the worktree for '{}', but its hook failed", branch)
+            }),
+        }
+    }
+
+    /// Delete a branch that no longer has a worktree, along with any stale registry entries
+    /// for it. Worktree directories are never touched. Returns the number of registry
+    /// entries removed.
+    pub fn prune_branch(&self, branch: &str, force: bool) -> Result<usize> {
+        if let Some(wt) = self.list_worktrees()?.iter().find(|wt| wt.branch == branch) {
+            anyhow::bail!(
+                "Branch '{}' still has a worktree at {} (use `maokai remove {}`)",
+                branch,
+                wt.path.display(),
+                branch
+            );
+        }
+
+        let stale: Vec<PathBuf> = load_registry()?
+            .into_iter()
+            .filter(|wt| wt.project_root == self.project_root && wt.branch == branch)
+            .map(|wt| wt.path)
+            .collect();
+
+        if self.branch_exists(branch)? {
+            let flag = if force { "-D" } else { "-d" };
+            let output = run_git(&self.project_root, ["branch", flag, branch])
+                .context("Failed to delete branch")?;
+
+            if !output.status.success() {
+                anyhow::bail!(
+                    "Failed to delete branch: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        } else if stale.is_empty() {
+            anyhow::bail!("Branch '{}' not found", branch);
+        }
+
+        for path in &stale {
+            remove_from_registry(path)?;
+        }
+
+        Ok(stale.len())
+    }
+
+    pub fn remove_worktree_at_path(&self, path: &Path, branch: &str, force: bool) -> Result<()> {
+        self.ensure_removable(path, force)?;
+
+        remove_info_file(path);
+
+        self.git_worktree_remove(path, force)?;
+
+        let _ = run_git(&self.project_root, ["branch", "-D", branch]);
+
+        remove_from_registry(path)?;
+        events::emit(Event::WorktreeRemoved { branch, path });
+        self.run_post_remove_hook(path, branch)
+    }
+
+    /// Registry entries, across all projects, whose directory is gone and which git no
+    /// longer lists as worktrees. Entries without a project root (from migrations) can't
+    /// be checked against git and are only included with `include_migrated`, when their
+    /// directory is gone. With `dry_run` nothing is removed. Returns the pruned entries.
+    pub fn prune_registry(
+        &self,
+        dry_run: bool,
+        include_migrated: bool,
+    ) -> Result<Vec<WorktreeInfo>> {
+        let mut known_to_git: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        let mut stale = Vec::new();
+
+        for wt in load_registry()? {
+            if wt.path.exists() {
+                continue;
+            }
+            if wt.project_root.as_os_str().is_empty() {
+                if include_migrated {
+                    stale.push(wt);
+                }
+                continue;
+            }
+
+            // A deleted repository has no worktrees left
+            let paths = match known_to_git.entry(wt.project_root.clone()) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    let paths = if wt.project_root.exists() {
+                        git_worktree_paths(&wt.project_root)?
+                    } else {
+                        Vec::new()
+                    };
+                    entry.insert(paths)
+                }
+            };
+            if !paths.contains(&wt.path) {
+                stale.push(wt);
+            }
+        }
+
+        if !dry_run && !stale.is_empty() {
+            let _lock = lock_registry()?;
+            let mut worktrees = load_registry()?;
+            worktrees.retain(|wt| !stale.iter().any(|s| s.id == wt.id));
+            debug!(pruned = stale.len(), "pruning registry entries");
+            save_registry(&worktrees)?;
+        }
+
+        Ok(stale)
+    }
+
+    /// Set the lifecycle status of the worktree with registry ID `id`.
+    pub fn set_status(&self, id: &str, status: WorktreeStatus) -> Result<()> {
+        let mut updated = None;
+        update_registry_entry(id, |wt| {
+            wt.status = status;
+            updated = Some(wt.clone());
+        })?;
+        let Some(updated) = updated else {
+            anyhow::bail!("Worktree with ID '{}' not found in the registry", id);
+        };
+        if updated.path.join(INFO_FILE_NAME).exists() {
+            write_info_file(&updated)?;
+        }
+        Ok(())
+    }
+
+    /// Undo a worktree this run just created: force-remove it and drop its registry
+    /// entry. The branch is deleted only if the worktree created it.
+    pub fn rollback_worktree(&self, worktree_info: &WorktreeInfo) -> Result<()> {
+        remove_info_file(&worktree_info.path);
+        self.git_worktree_remove(&worktree_info.path, true)?;
+        if worktree_info.base_branch.is_some() {
+            let _ = run_git(
+                &self.project_root,
+                ["branch", "-D", worktree_info.branch.as_str()],
+            );
+        }
+
+        remove_from_registry(&worktree_info.path)?;
+        events::emit(Event::WorktreeRemoved {
+            branch: &worktree_info.branch,
+            path: &worktree_info.path,
+        });
+        Ok(())
+    }
+
+    /// Run `git worktree remove`. A worktree left locked by a crashed operation is
+    /// unlocked and the removal retried: automatically with `force`, otherwise after
+    /// asking (non-interactive runs bail with a hint instead).
+    fn git_worktree_remove(&self, path: &Path, force: bool) -> Result<()> {
+        let path_arg = path.to_str().unwrap();
+        let mut args = vec!["worktree", "remove"];
+        if force {
+            args.push("--force");
+        }
+        args.push(path_arg);
+
+        let output = run_git(&self.project_root, &args).context("Failed to remove git worktree")?;
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !is_locked_error(&stderr) {
+            anyhow::bail!("Failed to remove worktree: {}", stderr);
+        }
+
+        let reason = stderr
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("fatal: ");
+        eprintln!("Worktree {} is locked ({}).", path.display(), reason);
+        if !force {
+            if !ui::is_interactive() && !ui::assume_yes() {
+                anyhow::bail!(
+                    "Worktree {} is locked; run `git worktree unlock {}` or pass --force",
+                    path.display(),
+                    path.display()
+                );
+            }
+            if !ui::confirm("Unlock it and retry?")? {
+                anyhow::bail!("Worktree {} is locked", path.display());
+            }
+        }
+
+        let output = run_git(&self.project_root, ["worktree", "unlock", path_arg])
+            .context("Failed to unlock git worktree")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to unlock worktree: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        eprintln!("Unlocked {}, retrying removal.", path.display());
+
+        let output = run_git(&self.project_root, &args).context("Failed to remove git worktree")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to remove worktree: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    /// Refuse to remove anything maokai did not create: the path must live under a
+    /// worktree base directory. The project's own checkout is never removable, and
+    /// `force` only relaxes the location check (e.g. after MAOKAI_WORKTREE_PATH changed).
+    fn ensure_removable(&self, path: &Path, force: bool) -> Result<()> {
+        let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        let target = canonical(path);
+
+        if target == canonical(&self.project_root) {
+            anyhow::bail!(
+                "Refusing to remove {}: it is the project's main checkout",
+                path.display()
+            );
+        }
+
+        if force {
+            return Ok(());
+        }
+
+        // A lock left by an interrupted `git worktree add` is handled when removing
+        if let Some(reason) = lock_reason(&self.project_root, path)?
+            && reason != GIT_ADD_LOCK_REASON
+        {
+            let reason = if reason.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", reason)
+            };
+            anyhow::bail!(
+                "Worktree {} is locked{}; run `maokai unlock` first or pass --force",
+                path.display(),
+                reason
+            );
+        }
+
+        let under_base = [
+            self.base_path.clone(),
+            get_worktree_base_path(),
+            workspaces_dir(),
+        ]
+        .iter()
+        .any(|base| target.starts_with(canonical(base)));
+        // Worktrees created at an explicit location (`create --path`) are still maokai's
+        let registered = || -> Result<bool> {
+            Ok(load_registry()?
+                .iter()
+                .any(|wt| canonical(&wt.path) == target))
+        };
+
+        if !under_base && !registered()? {
+            anyhow::bail!(
+                "Refusing to remove {}: it is outside maokai's worktree directories (use --force to override)",
+                path.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn branch_exists(&self, branch: &str) -> Result<bool> {
+        let output = run_git(
+            &self.project_root,
+            [
+                "show-ref",
+                "--verify",
+                "--quiet",
+                &format!("refs/heads/{}", branch),
+            ],
+        )
+        .context("Failed to check if branch exists")?;
 
         Ok(output.status.success())
     }
 
+    /// Resolve `base` (e.g. `main` or `upstream/main`) to the remote-tracking branch
+    /// `<remote>/<base>`, fetching it if it is not known locally yet.
+    fn resolve_remote_base(&self, remote: &str, base: &str, fetch: bool) -> Result<String> {
+        let output = run_git(&self.project_root, ["remote", "get-url", remote])
+            .context("Failed to look up remote")?;
+        if !output.status.success() {
+            anyhow::bail!("Remote '{}' not found", remote);
+        }
+
+        let name = base
+            .strip_prefix(remote)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .unwrap_or(base);
+        let remote_ref = format!("refs/remotes/{}/{}", remote, name);
+        let has_ref = || -> Result<bool> {
+            let output = run_git(
+                &self.project_root,
+                ["show-ref", "--verify", "--quiet", &remote_ref],
+            )
+            .context("Failed to check remote branch")?;
+            Ok(output.status.success())
+        };
+
+        if fetch || !has_ref()? {
+            let output = self.fetch_remote_branch(remote, name)?;
+            if !output.status.success() || !has_ref()? {
+                anyhow::bail!(
+                    "Branch '{}' not found on remote '{}': {}",
+                    name,
+                    remote,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
+
+        Ok(format!("{}/{}", remote, name))
+    }
+
+    /// Update `base` from its remote before branching off it (`create --fetch`). Only
+    /// remote-tracking bases like `origin/main` can be refreshed; local ones are left alone.
+    fn fetch_base(&self, base: &str) -> Result<()> {
+        let output = run_git(&self.project_root, ["remote"]).context("Failed to list remotes")?;
+        let remotes = String::from_utf8_lossy(&output.stdout);
+        let remote_branch = remotes.lines().find_map(|remote| {
+            base.strip_prefix(remote)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .map(|name| (remote, name))
+        });
+
+        let Some((remote, name)) = remote_branch else {
+            eprintln!(
+                "'{}' is a local branch, nothing to fetch (use --remote to base on a remote branch).",
+                base
+            );
+            return Ok(());
+        };
+
+        let output = self.fetch_remote_branch(remote, name)?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to fetch '{}' from '{}': {}",
+                name,
+                remote,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// Push the worktree's branch to `remote` with upstream tracking, and record it in
+    /// the registry.
+    pub fn push_branch(&self, worktree_info: &mut WorktreeInfo, remote: &str) -> Result<()> {
+        eprintln!("Pushing {} to {}...", worktree_info.branch, remote);
+        let output = run_git(
+            &worktree_info.path,
+            ["push", "-u", remote, worktree_info.branch.as_str()],
+        )
+        .context("Failed to run git push")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if is_auth_error(&stderr) {
+                anyhow::bail!(
+                    "Authentication to '{}' failed; check your credentials or SSH key ({})",
+                    remote,
+                    stderr.trim()
+                );
+            }
+            anyhow::bail!(
+                "Failed to push '{}' to '{}': {}",
+                worktree_info.branch,
+                remote,
+                stderr.trim()
+            );
+        }
+
+        worktree_info.pushed_to = Some(remote.to_string());
+        update_in_registry(worktree_info)?;
+        if worktree_info.path.join(INFO_FILE_NAME).exists() {
+            write_info_file(worktree_info)?;
+        }
+        Ok(())
+    }
+
+    fn fetch_remote_branch(&self, remote: &str, name: &str) -> Result<Output> {
+        eprintln!("Fetching {} from {}...", name, remote);
+        run_git(&self.project_root, ["fetch", remote, name]).context("Failed to fetch from remote")
+    }
+
+    /// Make sure `base` resolves to a commit, suggesting the closest branch name if not.
+    fn validate_base(&self, base: &str) -> Result<()> {
+        let output = run_git(
+            &self.project_root,
+            [
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{commit}}", base),
+            ],
+        )
+        .context("Failed to resolve base branch")?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let output = run_git(
+            &self.project_root,
+            ["branch", "--all", "--format=%(refname:short)"],
+        )
+        .context("Failed to list branches")?;
+        let branches = String::from_utf8_lossy(&output.stdout);
+
+        let suggestion = branches
+            .lines()
+            .map(|name| (edit_distance(base, name), name))
+            .filter(|(distance, name)| *distance <= (name.len() / 3).max(2))
+            .min_by_key(|(distance, _)| *distance);
+
+        match suggestion {
+            Some((_, name)) => {
+                anyhow::bail!("Base branch '{}' not found. Did you mean '{}'?", base, name)
+            }
+            None => anyhow::bail!("Base branch '{}' not found", base),
+        }
+    }
+
+    fn has_uncommitted_changes(&self) -> Result<bool> {
+        let output = run_git(&self.project_root, ["status", "--porcelain"])
+            .context("Failed to check working tree status")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to check working tree status: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(!output.stdout.is_empty())
+    }
+
+    fn restore_stash(&self) -> Result<()> {
+        let output = run_git(&self.project_root, ["stash", "pop", "--index", "stash@{0}"])
+            .context("Failed to restore stashed changes")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to restore stashed changes (they remain in stash@{{0}}): {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(())
+    }
+
     fn get_current_branch(&self) -> Result<String> {
-        let output = Command::new("git")
-            .args(["branch", "--show-current"])
-            .current_dir(&self.project_root)
-            .output()
+        let output = run_git(&self.project_root, ["branch", "--show-current"])
             .context("Failed to get current branch")?;
 
         if !output.status.success() {
@@ -316,54 +1566,411 @@ impl WorktreeManager {
         Ok(branch)
     }
 
-    fn get_project_name(&self) -> Result<String> {
-        if let Some(name) = self.project_root.file_name() {
-            Ok(name.to_string_lossy().to_string())
-        } else {
-            Ok("project".to_string())
+    /// Import any legacy `.maokai-info.json` files into the registry, even if the registry
+    /// already exists. Entries whose path is already registered are not duplicated.
+    pub fn migrate_legacy_info(&self) -> Result<Vec<WorktreeInfo>> {
+        import_legacy_info()
+    }
+
+    /// What [`Self::migrate_legacy_info`] would import and delete, without changing
+    /// anything. A missing registry is treated as empty rather than migrated.
+    pub fn plan_legacy_migration(&self) -> Result<MigrationPlan> {
+        let registered = read_registry()?.unwrap_or_default();
+        plan_migration(&registered)
+    }
+
+    /// Open a copy of the registry in `$EDITOR` and save it back only if it still parses.
+    /// On a parse error the original registry is left untouched.
+    pub fn edit_registry(&self) -> Result<()> {
+        let worktrees = load_registry()?;
+        let content = serde_json::to_string_pretty(&WorktreeRegistry { worktrees })
+            .context("Failed to serialize worktrees registry")?;
+
+        let temp_dir = tempfile::tempdir()?;
+        let temp_file = temp_dir.path().join("worktrees.json");
+        std::fs::write(&temp_file, &content)?;
+        open_in_editor(&temp_file)?;
+
+        let edited =
+            std::fs::read_to_string(&temp_file).context("Failed to read edited registry")?;
+        if edited == content {
+            eprintln!("Registry unchanged.");
+            return Ok(());
+        }
+
+        let registry: WorktreeRegistry = serde_json::from_str(&edited)
+            .context("Edited registry is invalid, original left unchanged")?;
+
+        // The lock isn't held while the editor is open, so check nothing changed meanwhile
+        let _lock = lock_registry()?;
+        let current = serde_json::to_string_pretty(&WorktreeRegistry {
+            worktrees: load_registry()?,
+        })
+        .context("Failed to serialize worktrees registry")?;
+        if current != content {
+            let mut kept = worktrees_registry_path().into_os_string();
+            kept.push(".edited");
+            let kept = PathBuf::from(kept);
+            std::fs::write(&kept, &edited).context("Failed to keep edited registry")?;
+            anyhow::bail!(
+                "The registry changed while it was being edited, so the edits were not saved; they are in {}",
+                kept.display()
+            );
+        }
+        save_registry(&registry.worktrees)?;
+        eprintln!("Registry saved ({} worktrees).", registry.worktrees.len());
+        Ok(())
+    }
+
+    /// Name used to prefix this project's worktrees. A name already recorded in the
+    /// registry for this project root is reused so existing worktrees keep resolving;
+    /// otherwise the directory name is used, disambiguated per `project_name_strategy`
+    /// when another project already claims it.
+    pub fn get_project_name(&self) -> Result<String> {
+        let basename = match self.project_root.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Ok("project".to_string()),
+        };
+
+        let strategy = load_config()?.project_name_strategy;
+        if strategy == ProjectNameStrategy::Basename {
+            return Ok(basename);
+        }
+
+        let registry = load_registry().unwrap_or_default();
+        if let Some(existing) = registry
+            .iter()
+            .find(|wt| wt.project_root == self.project_root)
+        {
+            return Ok(existing.project_name.clone());
+        }
+
+        let collides = registry.iter().any(|wt| {
+            wt.project_name == basename
+                && wt.project_root != self.project_root
+                && !wt.project_root.as_os_str().is_empty()
+        });
+        if !collides {
+            return Ok(basename);
+        }
+
+        let parent = self
+            .project_root
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string());
+
+        match (strategy, parent) {
+            (ProjectNameStrategy::Parent, Some(parent)) => Ok(format!("{}-{}", parent, basename)),
+            _ => Ok(format!(
+                "{}-{}",
+                basename,
+                short_path_hash(&self.project_root)
+            )),
         }
     }
 
     fn sanitize_branch_name(&self, branch: &str) -> String {
-        branch
-            .replace('/', "-")
-            .replace('\\', "-")
-            .replace(':', "-")
-            .replace('*', "-")
-            .replace('?', "-")
-            .replace('"', "-")
-            .replace('<', "-")
-            .replace('>', "-")
-            .replace('|', "-")
-            .replace(' ', "-")
-    }
-
-    pub fn get_worktree_path(&self, branch: &str) -> PathBuf {
-        let project_name = self
-            .get_project_name()
-            .unwrap_or_else(|_| "project".to_string());
+        branch.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|', ' '], "-")
+    }
+
+    /// Where a new worktree for `branch` goes: `<base>/<project>-<branch>`, or
+    /// `<base>/<agent>/<project>-<branch>` when worktrees are grouped per agent.
+    pub fn get_worktree_path(
+        &self,
+        branch: &str,
+        agent: &str,
+        per_agent_dir: bool,
+    ) -> Result<PathBuf> {
+        let project_name = self.get_project_name()?;
         let safe_branch_name = self.sanitize_branch_name(branch);
         let worktree_name = format!("{}-{}", project_name, safe_branch_name);
-        self.base_path.join(&worktree_name)
+
+        let base = self.worktree_base()?;
+        let base = if per_agent_dir {
+            base.join(self.sanitize_branch_name(agent))
+        } else {
+            base
+        };
+        Ok(base.join(worktree_name))
+    }
+
+    /// Directory new worktrees of this project go in: `worktree_base` from the repo's
+    /// `.maokai.toml`, resolved against the project root, else the global base path.
+    /// A configured base is created and made canonical, matching how git reports
+    /// worktree paths (e.g. for `../worktrees`).
+    fn worktree_base(&self) -> Result<PathBuf> {
+        let Some(base) = load_repo_config(&self.project_root)?.worktree_base else {
+            return Ok(self.base_path.clone());
+        };
+        let base = self.project_root.join(expand_path(&base));
+        std::fs::create_dir_all(&base)
+            .with_context(|| format!("Failed to create worktree_base {}", base.display()))?;
+        Ok(base.canonicalize()?)
     }
 }
 
+/// Paths of all worktrees git knows for the repository checked out at `dir`.
+fn git_worktree_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let output = run_git(dir, ["worktree", "list", "--porcelain"])
+        .context("Failed to list git worktrees")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to list git worktrees: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix("worktree "))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Reason git records while `git worktree add` runs; a worktree still locked with it was
+/// left behind by an interrupted add.
+const GIT_ADD_LOCK_REASON: &str = "initializing";
+
+/// The lock reason of the worktree at `path` (empty if locked without one), or None if
+/// it isn't locked. `git_dir` is any checkout of the repository.
+fn lock_reason(git_dir: &Path, path: &Path) -> Result<Option<String>> {
+    let output = run_git(git_dir, ["worktree", "list", "--porcelain"])
+        .context("Failed to list git worktrees")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to list git worktrees: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let target = canonical(path);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let entry = stdout.split("\n\n").find(|chunk| {
+        chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("worktree "))
+            .is_some_and(|p| canonical(Path::new(p)) == target)
+    });
+
+    Ok(entry.and_then(|chunk| {
+        chunk.lines().find_map(|line| {
+            line.strip_prefix("locked")
+                .map(|reason| reason.trim_start().to_string())
+        })
+    }))
+}
+
+/// Whether `git worktree remove` failed because the worktree is locked. Newer git says
+/// "cannot remove a locked working tree", older versions "'<path>' is locked".
+fn is_locked_error(stderr: &str) -> bool {
+    stderr.contains("locked working tree") || stderr.contains("is locked")
+}
+
+/// Ignored files larger than this are not copied by `create --copy-ignored`.
+pub const COPY_IGNORED_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Progress lines git prints to stderr on success that carry no useful information.
+const GIT_STDERR_CHATTER: &[&str] = &["Preparing worktree", "HEAD is now at", "Updating files:"];
+
+/// Git commands whose stderr on success is only transfer progress (`Enumerating objects`,
+/// `remote: ...`, `To ...`). Their callers show stderr when they fail.
+const GIT_PROGRESS_COMMANDS: &[&str] = &["push", "fetch"];
+
+/// Whether `git push` failed because the remote rejected or asked for credentials.
+fn is_auth_error(stderr: &str) -> bool {
+    const MARKERS: [&str; 5] = [
+        "Authentication failed",
+        "Permission denied",
+        "could not read Username",
+        "terminal prompts disabled",
+        "returned error: 403",
+    ];
+    MARKERS.iter().any(|marker| stderr.contains(marker))
+}
+
+/// Run git in `dir`. When git succeeds but still writes to stderr (detached HEAD, LFS,
+/// config warnings), those lines are forwarded to our stderr instead of being dropped,
+/// except for push and fetch progress.
+/// Every git invocation goes through here, so the executable (see [`git_program`]) is
+/// resolved once per process.
+fn run_git<I, S>(dir: &Path, args: I) -> std::io::Result<Output>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    static GIT: OnceLock<PathBuf> = OnceLock::new();
+    let git = GIT.get_or_init(git_program);
+
+    let args: Vec<OsString> = args.into_iter().map(|a| a.as_ref().to_owned()).collect();
+    debug!(dir = %dir.display(), git = %git.display(), ?args, "running git");
+    let output = Command::new(git)
+        .args(&args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", git.display(), e)))?;
+    debug!(status = ?output.status.code(), "git finished");
+
+    let progress_only = args
+        .first()
+        .is_some_and(|command| GIT_PROGRESS_COMMANDS.iter().any(|c| command == c));
+    if output.status.success() && !progress_only {
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            let line = line.trim_end();
+            if !line.is_empty() && !GIT_STDERR_CHATTER.iter().any(|c| line.starts_with(c)) {
+                eprintln!("git: {}", line);
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Recursively copy `src` into `dest`, skipping `.git` and, unless `overwrite` is set,
+/// any file that already exists at the destination. Returns the number of files copied.
+fn copy_dir_recursive(src: &Path, dest: &Path, overwrite: bool) -> Result<usize> {
+    std::fs::create_dir_all(dest)?;
+    let mut copied = 0;
+
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name() == ".git" {
+            continue;
+        }
+
+        let target = dest.join(entry.file_name());
+        if path.is_dir() {
+            copied += copy_dir_recursive(&path, &target, overwrite)?;
+        } else if overwrite || !target.exists() {
+            debug!(from = %path.display(), to = %target.display(), "copying template file");
+            std::fs::copy(&path, &target)?;
+            copied += 1;
+        }
+    }
+
+    Ok(copied)
+}
+
+/// Total size in bytes of the files under `path` (symlinks are not followed).
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    std::fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|e| disk_usage(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Levenshtein distance between two strings, used for "did you mean" suggestions.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+
+    prev[b.len()]
+}
+
+/// Stable 6-character FNV-1a hash of a path, used to disambiguate project names.
+fn short_path_hash(path: &Path) -> String {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in path.to_string_lossy().bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    format!("{:06x}", hash & 0xffffff)
+}
+
 // Registry functions
 
 fn load_registry() -> Result<Vec<WorktreeInfo>> {
-    let registry_path = worktrees_registry_path();
+    match read_registry()? {
+        Some(worktrees) => Ok(worktrees),
+        None if auto_migrate_disabled() => Ok(Vec::new()),
+        // Attempt migration from old .maokai-info.json files
+        None => migrate_old_worktree_info(),
+    }
+}
+
+fn auto_migrate_disabled() -> bool {
+    std::env::var_os(NO_AUTO_MIGRATE_ENV).is_some_and(|value| !value.is_empty())
+}
 
+/// Read the registry file, or None if it doesn't exist yet.
+fn read_registry() -> Result<Option<Vec<WorktreeInfo>>> {
+    let registry_path = worktrees_registry_path();
     if !registry_path.exists() {
-        // Attempt migration from old .maokai-info.json files
-        return migrate_old_worktree_info();
+        return Ok(None);
     }
 
-    let content = std::fs::read_to_string(&registry_path)
-        .context("Failed to read worktrees registry")?;
+    let content =
+        std::fs::read_to_string(&registry_path).context("Failed to read worktrees registry")?;
     let registry: WorktreeRegistry =
         serde_json::from_str(&content).context("Failed to parse worktrees registry")?;
 
-    Ok(registry.worktrees)
+    let mut worktrees = dedup_by_path(registry.worktrees);
+    for wt in &mut worktrees {
+        if wt.worktree_dir_name.is_empty() {
+            wt.worktree_dir_name = dir_name_of(&wt.path);
+        }
+    }
+    Ok(Some(worktrees))
+}
+
+fn dir_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Collapse entries that point at the same worktree (compared by canonical path),
+/// keeping the most recently created one. Duplicates can come from old migrations or
+/// manual edits; they are dropped for good on the next registry write.
+fn dedup_by_path(worktrees: Vec<WorktreeInfo>) -> Vec<WorktreeInfo> {
+    let key = |wt: &WorktreeInfo| std::fs::canonicalize(&wt.path).unwrap_or(wt.path.clone());
+
+    let mut deduped: Vec<(PathBuf, WorktreeInfo)> = Vec::with_capacity(worktrees.len());
+    let mut duplicates = 0;
+
+    for wt in worktrees {
+        let path = key(&wt);
+        match deduped.iter_mut().find(|(existing, _)| *existing == path) {
+            Some((_, kept)) => {
+                duplicates += 1;
+                if wt.created_at > kept.created_at {
+                    *kept = wt;
+                }
+            }
+            None => deduped.push((path, wt)),
+        }
+    }
+
+    // The registry is loaded several times per command; only warn once
+    static WARNED: Once = Once::new();
+    if duplicates > 0 {
+        WARNED.call_once(|| {
+            eprintln!(
+                "Warning: ignoring {} duplicate registry entries (kept the newest per path)",
+                duplicates
+            );
+        });
+    }
+
+    deduped.into_iter().map(|(_, wt)| wt).collect()
 }
 
 fn save_registry(worktrees: &[WorktreeInfo]) -> Result<()> {
@@ -377,135 +1984,480 @@ fn save_registry(worktrees: &[WorktreeInfo]) -> Result<()> {
     let registry = WorktreeRegistry {
         worktrees: worktrees.to_vec(),
     };
-    let content =
-        serde_json::to_string_pretty(&registry).context("Failed to serialize worktrees registry")?;
-    std::fs::write(&registry_path, content).context("Failed to write worktrees registry")?;
+    let content = serde_json::to_string_pretty(&registry)
+        .context("Failed to serialize worktrees registry")?;
+    debug!(path = %registry_path.display(), entries = worktrees.len(), "writing registry");
+    // Write a sibling file and rename it over the registry, so readers that don't take
+    // the lock never see a half-written file
+    let dir = registry_path.parent().unwrap_or(Path::new("."));
+    let mut temp =
+        tempfile::NamedTempFile::new_in(dir).context("Failed to write worktrees registry")?;
+    temp.write_all(content.as_bytes())
+        .context("Failed to write worktrees registry")?;
+    temp.persist(&registry_path)
+        .context("Failed to write worktrees registry")?;
     Ok(())
 }
 
+/// Take an exclusive advisory lock on `worktrees.json.lock` next to the registry,
+/// released when the returned file is dropped. Held for a whole read-modify-write cycle
+/// so concurrent maokai processes (and threads, e.g. parallel workspace creation) don't
+/// overwrite each other's changes. Must not be nested: a second lock from the same
+/// process would wait forever.
+fn lock_registry() -> Result<std::fs::File> {
+    let mut lock_path = worktrees_registry_path().into_os_string();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+    if let Some(parent) = lock_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open registry lock {}", lock_path.display()))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+    Ok(file)
+}
+
 fn add_to_registry(info: &WorktreeInfo) -> Result<()> {
+    let _lock = lock_registry()?;
     let mut worktrees = load_registry().unwrap_or_default();
+    debug!(id = %info.id, branch = %info.branch, path = %info.path.display(), "adding registry entry");
     worktrees.push(info.clone());
     save_registry(&worktrees)
 }
 
+/// Apply `update` to the entry with registry ID `id`. Returns false if there is none.
+fn update_registry_entry(id: &str, update: impl FnOnce(&mut WorktreeInfo)) -> Result<bool> {
+    let _lock = lock_registry()?;
+    let mut worktrees = load_registry()?;
+    let Some(entry) = worktrees.iter_mut().find(|wt| wt.id == id) else {
+        return Ok(false);
+    };
+    debug!(id, "updating registry entry");
+    update(entry);
+    save_registry(&worktrees)?;
+    Ok(true)
+}
+
+/// Replace the entry with the same path as `info`.
+fn update_in_registry(info: &WorktreeInfo) -> Result<()> {
+    let _lock = lock_registry()?;
+    let mut worktrees = load_registry().unwrap_or_default();
+    debug!(id = %info.id, path = %info.path.display(), "updating registry entry");
+    for wt in worktrees.iter_mut().filter(|wt| wt.path == info.path) {
+        *wt = info.clone();
+    }
+    save_registry(&worktrees)
+}
+
 fn remove_from_registry(path: &Path) -> Result<()> {
+    let _lock = lock_registry()?;
     let mut worktrees = load_registry().unwrap_or_default();
+    debug!(path = %path.display(), "removing registry entry");
     worktrees.retain(|wt| wt.path != path);
     save_registry(&worktrees)
 }
 
-/// Migrate old .maokai-info.json files from worktrees to the central registry.
-fn migrate_old_worktree_info() -> Result<Vec<WorktreeInfo>> {
-    use crate::config::get_worktree_base_path;
+const INFO_FILE_NAME: &str = ".maokai-info.json";
 
-    let base_path = get_worktree_base_path();
-    let mut migrated = Vec::new();
-
-    if !base_path.exists() {
-        return Ok(migrated);
-    }
-
-    // Scan for .maokai-info.json files in worktree directories
-    if let Ok(entries) = std::fs::read_dir(&base_path) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                let info_path = path.join(".maokai-info.json");
-                if info_path.exists() {
-                    if let Ok(content) = std::fs::read_to_string(&info_path) {
-                        // Try to parse as old format (without project_root)
-                        #[derive(Deserialize)]
-                        struct OldWorktreeInfo {
-                            id: String,
-                            branch: String,
-                            path: PathBuf,
-                            project_name: String,
-                            agent: String,
-                            created_at: chrono::DateTime<chrono::Utc>,
-                            status: WorktreeStatus,
-                        }
-
-                        if let Ok(old_info) = serde_json::from_str::<OldWorktreeInfo>(&content) {
-                            // Convert to new format with empty project_root (we don't know it)
-                            let new_info = WorktreeInfo {
-                                id: old_info.id,
-                                branch: old_info.branch,
-                                path: old_info.path,
-                                project_root: PathBuf::new(), // Unknown for migrated entries
-                                project_name: old_info.project_name,
-                                agent: old_info.agent,
-                                created_at: old_info.created_at,
-                                status: old_info.status,
-                            };
-                            migrated.push(new_info);
-
-                            // Delete the old .maokai-info.json file
-                            let _ = std::fs::remove_file(&info_path);
-                        }
-                    }
-                }
+/// Environment variable that, when set, stops a missing registry from being seeded from
+/// legacy info files on first use; `maokai migrate` still imports them.
+pub const NO_AUTO_MIGRATE_ENV: &str = "MAOKAI_NO_AUTO_MIGRATE";
+
+/// Write `<worktree>/.maokai-info.json` mirroring the registry entry, for tooling built
+/// against the pre-registry layout. Only done when `emit_worktree_info` is enabled. The
+/// file is added to the repo's `info/exclude` so it never shows up as untracked.
+fn write_info_file(info: &WorktreeInfo) -> Result<()> {
+    if !load_config()?.emit_worktree_info {
+        return Ok(());
+    }
+
+    let content =
+        serde_json::to_string_pretty(info).context("Failed to serialize worktree info")?;
+    std::fs::write(info.path.join(INFO_FILE_NAME), content)
+        .context("Failed to write worktree info file")?;
+
+    let output = run_git(&info.path, ["rev-parse", "--git-common-dir"])
+        .context("Failed to locate git directory")?;
+    if output.status.success() {
+        let common_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let exclude_path = info.path.join(common_dir).join("info").join("exclude");
+        let existing = std::fs::read_to_string(&exclude_path).unwrap_or_default();
+        if !existing.lines().any(|line| line.trim() == INFO_FILE_NAME) {
+            if let Some(parent) = exclude_path.parent() {
+                std::fs::create_dir_all(parent)?;
             }
+            let separator = if existing.is_empty() || existing.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            std::fs::write(
+                &exclude_path,
+                format!("{}{}{}\n", existing, separator, INFO_FILE_NAME),
+            )
+            .context("Failed to update git info/exclude")?;
         }
     }
 
-    // Also check workspaces directory for old .maokai-info.json files
-    let workspaces_dir = crate::config::workspaces_dir();
-    if workspaces_dir.exists() {
-        if let Ok(workspace_entries) = std::fs::read_dir(&workspaces_dir) {
-            for workspace_entry in workspace_entries.flatten() {
-                let workspace_path = workspace_entry.path();
-                if workspace_path.is_dir() {
-                    // Check subdirectories within each workspace
-                    if let Ok(project_entries) = std::fs::read_dir(&workspace_path) {
-                        for project_entry in project_entries.flatten() {
-                            let project_path = project_entry.path();
-                            if project_path.is_dir() {
-                                let info_path = project_path.join(".maokai-info.json");
-                                if info_path.exists() {
-                                    if let Ok(content) = std::fs::read_to_string(&info_path) {
-                                        #[derive(Deserialize)]
-                                        struct OldWorktreeInfo {
-                                            id: String,
-                                            branch: String,
-                                            path: PathBuf,
-                                            project_name: String,
-                                            agent: String,
-                                            created_at: chrono::DateTime<chrono::Utc>,
-                                            status: WorktreeStatus,
-                                        }
-
-                                        if let Ok(old_info) =
-                                            serde_json::from_str::<OldWorktreeInfo>(&content)
-                                        {
-                                            let new_info = WorktreeInfo {
-                                                id: old_info.id,
-                                                branch: old_info.branch,
-                                                path: old_info.path,
-                                                project_root: PathBuf::new(),
-                                                project_name: old_info.project_name,
-                                                agent: old_info.agent,
-                                                created_at: old_info.created_at,
-                                                status: old_info.status,
-                                            };
-                                            migrated.push(new_info);
-
-                                            let _ = std::fs::remove_file(&info_path);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    Ok(())
+}
+
+fn remove_info_file(worktree_path: &Path) {
+    let _ = std::fs::remove_file(worktree_path.join(INFO_FILE_NAME));
+}
+
+/// A worktree found in a legacy per-worktree `.maokai-info.json` file.
+pub struct LegacyInfo {
+    pub info_path: PathBuf,
+    pub info: WorktreeInfo,
+}
+
+/// What a migration of legacy info files does, worked out before anything changes.
+pub struct MigrationPlan {
+    /// Info files whose worktrees are not in the registry yet
+    pub entries: Vec<LegacyInfo>,
+    /// Whether imported info files are deleted (they are kept with `emit_worktree_info`)
+    pub delete_info_files: bool,
+}
+
+/// Old per-worktree info format (before the central registry, without project_root).
+#[derive(Deserialize)]
+struct OldWorktreeInfo {
+    id: String,
+    branch: String,
+    path: PathBuf,
+    project_name: String,
+    agent: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    status: WorktreeStatus,
+}
+
+fn read_legacy_info(dir: &Path) -> Option<LegacyInfo> {
+    let info_path = dir.join(INFO_FILE_NAME);
+    let content = std::fs::read_to_string(&info_path).ok()?;
+    let old_info = serde_json::from_str::<OldWorktreeInfo>(&content).ok()?;
+
+    // Convert to new format with empty project_root (we don't know it)
+    let info = WorktreeInfo {
+        worktree_dir_name: dir_name_of(&old_info.path),
+        id: old_info.id,
+        branch: old_info.branch,
+        path: old_info.path,
+        project_root: PathBuf::new(), // Unknown for migrated entries
+        project_name: old_info.project_name,
+        agent: old_info.agent,
+        created_at: old_info.created_at,
+        status: old_info.status,
+        base_branch: None,
+        pushed_to: None,
+        issue: None,
+    };
+    Some(LegacyInfo { info_path, info })
+}
+
+/// Scan worktree directories (`<base>/*` and `workspaces/*/*`) for legacy info files.
+fn scan_legacy_info() -> Vec<LegacyInfo> {
+    use crate::config::get_worktree_base_path;
+
+    let subdirs = |dir: &Path| -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.is_dir())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut found = Vec::new();
+
+    for dir in subdirs(&get_worktree_base_path()) {
+        found.extend(read_legacy_info(&dir));
+    }
+
+    // Workspace worktrees live one level deeper, inside each workspace directory
+    for workspace in subdirs(&crate::config::workspaces_dir()) {
+        for dir in subdirs(&workspace) {
+            found.extend(read_legacy_info(&dir));
         }
     }
 
-    // Save migrated entries to the new registry if any were found
-    if !migrated.is_empty() {
-        save_registry(&migrated)?;
+    found
+}
+
+/// Find the legacy info files whose worktree path isn't in `registered`. Only reads:
+/// applying the plan is left to [`apply_migration`]. Imported files are deleted unless
+/// `emit_worktree_info` is enabled, in which case info files are expected to exist.
+fn plan_migration(registered: &[WorktreeInfo]) -> Result<MigrationPlan> {
+    let delete_info_files = !load_config()?.emit_worktree_info;
+    let entries = scan_legacy_info()
+        .into_iter()
+        .filter(|legacy| !registered.iter().any(|wt| wt.path == legacy.info.path))
+        .collect();
+    Ok(MigrationPlan {
+        entries,
+        delete_info_files,
+    })
+}
+
+/// Add the plan's entries to `worktrees`, save the registry and delete the imported info
+/// files. Returns the newly added entries.
+fn apply_migration(
+    mut worktrees: Vec<WorktreeInfo>,
+    plan: MigrationPlan,
+) -> Result<Vec<WorktreeInfo>> {
+    if plan.entries.is_empty() {
+        return Ok(Vec::new());
     }
 
+    let migrated: Vec<WorktreeInfo> = plan.entries.iter().map(|e| e.info.clone()).collect();
+    worktrees.extend(migrated.iter().cloned());
+    save_registry(&worktrees)?;
+
+    for legacy in &plan.entries {
+        debug!(info_file = %legacy.info_path.display(), branch = %legacy.info.branch, "migrated legacy info");
+        if plan.delete_info_files {
+            let _ = std::fs::remove_file(&legacy.info_path);
+        }
+    }
     Ok(migrated)
 }
+
+/// Migrate old .maokai-info.json files from worktrees to the central registry.
+/// Runs implicitly the first time the registry is loaded and doesn't exist yet, unless
+/// `MAOKAI_NO_AUTO_MIGRATE` is set.
+fn migrate_old_worktree_info() -> Result<Vec<WorktreeInfo>> {
+    apply_migration(Vec::new(), plan_migration(&[])?)
+}
+
+/// Import legacy info files into an existing registry, skipping worktrees whose path is
+/// already registered. Returns the newly added entries.
+fn import_legacy_info() -> Result<Vec<WorktreeInfo>> {
+    let _lock = lock_registry()?;
+    let worktrees = read_registry()?.unwrap_or_default();
+    let plan = plan_migration(&worktrees)?;
+    apply_migration(worktrees, plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(n: usize) -> WorktreeInfo {
+        WorktreeInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            branch: format!("branch-{}", n),
+            path: PathBuf::from(format!("/nonexistent/maokai-test/wt-{}", n)),
+            project_root: PathBuf::from("/nonexistent/maokai-test/project"),
+            project_name: "project".to_string(),
+            agent: "claude".to_string(),
+            created_at: chrono::Utc::now(),
+            status: WorktreeStatus::Active,
+            base_branch: None,
+            worktree_dir_name: format!("wt-{}", n),
+            pushed_to: None,
+            issue: None,
+        }
+    }
+
+    #[test]
+    fn concurrent_registry_adds_are_not_lost() {
+        let home = crate::config::test_home();
+        assert!(worktrees_registry_path().starts_with(home));
+
+        let handles: Vec<_> = (0..16)
+            .map(|n| std::thread::spawn(move || add_to_registry(&entry(n))))
+            .collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        // Other tests share the registry; only look at this test's entries
+        let mut branches: Vec<_> = load_registry()
+            .unwrap()
+            .into_iter()
+            .filter(|wt| wt.project_root == entry(0).project_root)
+            .map(|wt| wt.branch)
+            .collect();
+        branches.sort();
+        let mut expected: Vec<_> = (0..16).map(|n| format!("branch-{}", n)).collect();
+        expected.sort();
+        assert_eq!(branches, expected);
+    }
+
+    #[test]
+    fn status_json_field_names() {
+        let report = WorktreeStatusReport {
+            worktree: entry(0),
+            git_state: Some(GitSummary {
+                current_branch: Some("branch-0".to_string()),
+                dirty: true,
+                changed_files: 2,
+                upstream: Some("origin/branch-0".to_string()),
+                ahead: 1,
+                behind: 0,
+                locked: None,
+            }),
+        };
+        let json = serde_json::to_value(&report).unwrap();
+
+        // Scripts rely on these names; changing them is a breaking change
+        let keys = |value: &serde_json::Value| {
+            let mut keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(
+            keys(&json),
+            [
+                "agent",
+                "base_branch",
+                "branch",
+                "created_at",
+                "git_state",
+                "id",
+                "path",
+                "project_name",
+                "project_root",
+                "status",
+                "worktree_dir_name",
+            ]
+        );
+        assert_eq!(
+            keys(&json["git_state"]),
+            [
+                "ahead",
+                "behind",
+                "changed_files",
+                "current_branch",
+                "dirty",
+                "locked",
+                "upstream",
+            ]
+        );
+        assert_eq!(json["status"], "Active");
+
+        // Optional fields appear only when set
+        let mut worktree = entry(1);
+        worktree.pushed_to = Some("origin".to_string());
+        worktree.issue = Some("#12".to_string());
+        let json = serde_json::to_value(WorktreeStatusReport {
+            worktree,
+            git_state: None,
+        })
+        .unwrap();
+        assert_eq!(json["pushed_to"], "origin");
+        assert_eq!(json["issue"], "#12");
+        assert!(json["git_state"].is_null());
+    }
+
+    fn branches(worktrees: &[WorktreeInfo]) -> Vec<&str> {
+        worktrees.iter().map(|wt| wt.branch.as_str()).collect()
+    }
+
+    #[test]
+    fn sort_by_created_defaults_to_newest_first() {
+        let now = chrono::Utc::now();
+        let mut worktrees: Vec<_> = (0..3)
+            .map(|n| {
+                let mut wt = entry(n);
+                wt.created_at = now - chrono::Duration::days(n as i64);
+                wt
+            })
+            .collect();
+
+        sort_worktrees(&mut worktrees, SortBy::Created, None);
+        assert_eq!(branches(&worktrees), ["branch-0", "branch-1", "branch-2"]);
+        sort_worktrees(&mut worktrees, SortBy::Created, Some(SortOrder::Asc));
+        assert_eq!(branches(&worktrees), ["branch-2", "branch-1", "branch-0"]);
+    }
+
+    #[test]
+    fn sort_by_names_defaults_to_a_to_z() {
+        let mut worktrees: Vec<_> = [("web", "b"), ("api", "z"), ("web", "a")]
+            .into_iter()
+            .enumerate()
+            .map(|(n, (project, branch))| {
+                let mut wt = entry(n);
+                wt.project_name = project.to_string();
+                wt.branch = branch.to_string();
+                wt
+            })
+            .collect();
+
+        sort_worktrees(&mut worktrees, SortBy::Branch, None);
+        assert_eq!(branches(&worktrees), ["a", "b", "z"]);
+        sort_worktrees(&mut worktrees, SortBy::Branch, Some(SortOrder::Desc));
+        assert_eq!(branches(&worktrees), ["z", "b", "a"]);
+
+        // Ties within a project are broken by branch
+        sort_worktrees(&mut worktrees, SortBy::Project, None);
+        assert_eq!(branches(&worktrees), ["z", "a", "b"]);
+        sort_worktrees(&mut worktrees, SortBy::Project, Some(SortOrder::Desc));
+        assert_eq!(branches(&worktrees), ["b", "a", "z"]);
+    }
+
+    #[test]
+    fn sort_by_activity_uses_last_commit_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = |name: &str, date: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            let git = |args: &[&str]| {
+                let status = std::process::Command::new("git")
+                    .args(args)
+                    .current_dir(&path)
+                    .env("GIT_COMMITTER_DATE", date)
+                    .env("GIT_AUTHOR_DATE", date)
+                    .stdout(std::process::Stdio::null())
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            };
+            git(&["init", "-q"]);
+            git(&[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "c",
+            ]);
+            path
+        };
+
+        let mut worktrees: Vec<_> = [
+            ("old", Some(repo("old", "2020-01-01T00:00:00Z"))),
+            ("missing", None),
+            ("new", Some(repo("new", "2024-01-01T00:00:00Z"))),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(n, (branch, path))| {
+            let mut wt = entry(n);
+            wt.branch = branch.to_string();
+            if let Some(path) = path {
+                wt.path = path;
+            }
+            wt
+        })
+        .collect();
+
+        // Missing worktrees count as oldest
+        sort_worktrees(&mut worktrees, SortBy::Activity, None);
+        assert_eq!(branches(&worktrees), ["new", "old", "missing"]);
+        sort_worktrees(&mut worktrees, SortBy::Activity, Some(SortOrder::Asc));
+        assert_eq!(branches(&worktrees), ["missing", "old", "new"]);
+    }
+}